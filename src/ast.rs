@@ -5,10 +5,11 @@ pub trait Node {
     fn print(&self) -> String;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StatementNode {
     Let(LetStatement),
     Return(ReturnStatement),
+    Expression(ExpressionStatement),
 }
 
 impl Node for StatementNode {
@@ -16,6 +17,7 @@ impl Node for StatementNode {
         match self {
             StatementNode::Let(e) => e.token_literal(),
             StatementNode::Return(e) => e.token_literal(),
+            StatementNode::Expression(e) => e.token_literal(),
         }
     }
 
@@ -23,44 +25,75 @@ impl Node for StatementNode {
         match self {
             StatementNode::Let(e) => e.print(),
             StatementNode::Return(e) => e.print(),
+            StatementNode::Expression(e) => e.print(),
         }
     }
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ExpressionNode {
-    IdentifierNode(Identifier)
+    IdentifierNode(Identifier),
+    Integer(IntegerLiteral),
+    String(StringLiteral),
+    Prefix(PrefixExpression),
+    Infix(InfixExpression),
+    Boolean(BooleanLiteral),
+    If(IfExpression),
+    Function(FunctionLiteral),
+    Call(CallExpression),
+    Array(ArrayLiteral),
+    Index(IndexExpression),
+    Hash(HashLiteral),
+    Macro(MacroLiteral),
 }
 
 impl Node for ExpressionNode {
     fn token_literal(&self) -> String {
         match self {
-            ExpressionNode::IdentifierNode(e) => e.token_literal()
+            ExpressionNode::IdentifierNode(e) => e.token_literal(),
+            ExpressionNode::Integer(e) => e.token_literal(),
+            ExpressionNode::String(e) => e.token_literal(),
+            ExpressionNode::Prefix(e) => e.token_literal(),
+            ExpressionNode::Infix(e) => e.token_literal(),
+            ExpressionNode::Boolean(e) => e.token_literal(),
+            ExpressionNode::If(e) => e.token_literal(),
+            ExpressionNode::Function(e) => e.token_literal(),
+            ExpressionNode::Call(e) => e.token_literal(),
+            ExpressionNode::Array(e) => e.token_literal(),
+            ExpressionNode::Index(e) => e.token_literal(),
+            ExpressionNode::Hash(e) => e.token_literal(),
+            ExpressionNode::Macro(e) => e.token_literal(),
         }
     }
 
     fn print(&self) -> String {
         match self {
-            ExpressionNode::IdentifierNode(e) => e.print()
+            ExpressionNode::IdentifierNode(e) => e.print(),
+            ExpressionNode::Integer(e) => e.print(),
+            ExpressionNode::String(e) => e.print(),
+            ExpressionNode::Prefix(e) => e.print(),
+            ExpressionNode::Infix(e) => e.print(),
+            ExpressionNode::Boolean(e) => e.print(),
+            ExpressionNode::If(e) => e.print(),
+            ExpressionNode::Function(e) => e.print(),
+            ExpressionNode::Call(e) => e.print(),
+            ExpressionNode::Array(e) => e.print(),
+            ExpressionNode::Index(e) => e.print(),
+            ExpressionNode::Hash(e) => e.print(),
+            ExpressionNode::Macro(e) => e.print(),
         }
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct Program {
     pub statements: Vec<StatementNode>,
 }
 
 impl Node for Program {
     fn token_literal(&self) -> String {
-        if self.statements.len() > 0 {
-            match &self.statements[0] {
-                StatementNode::Let(e) => e.token_literal(),
-                StatementNode::Return(e) => e.token_literal()
-            }
-        } else {
-            "".to_string()
-        }
+        self.statements.first().map(|statement| statement.token_literal()).unwrap_or_default()
     }
 
     fn print(&self) -> String {
@@ -72,7 +105,7 @@ impl Node for Program {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LetStatement {
     pub token: Token,
     pub name: Identifier,
@@ -87,19 +120,18 @@ impl Node for LetStatement {
     fn print(&self) -> String {
         let mut output = String::new();
         output.push_str(&self.token_literal());
-        output.push_str(" ");
+        output.push(' ');
         output.push_str(&self.name.print());
-        output.push_str(" = ");
-        match &self.value {
-            Some(value) => output.push_str(&value.print()),
-            None => output.push_str("None")
+        if let Some(value) = &self.value {
+            output.push_str(" = ");
+            output.push_str(&value.print());
         }
-        output.push_str(";");
+        output.push(';');
         output
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Identifier {
     pub token: Token,
     pub value: String,
@@ -115,7 +147,7 @@ impl Node for Identifier {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ReturnStatement {
     pub token: Token,
     pub return_value: Option<ExpressionNode>,
@@ -129,12 +161,292 @@ impl Node for ReturnStatement {
     fn print(&self) -> String {
         let mut output = String::new();
         output.push_str(&self.token_literal());
-        output.push_str(" ");
-        match &self.return_value {
-            Some(value) => output.push_str(&value.print()),
-            None => output.push_str("None")
+        if let Some(value) = &self.return_value {
+            output.push(' ');
+            output.push_str(&value.print());
+        }
+        output.push(';');
+        output
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ExpressionStatement {
+    pub token: Token,
+    pub expression: Option<ExpressionNode>,
+}
+
+impl Node for ExpressionStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        match &self.expression {
+            Some(expression) => expression.print(),
+            None => String::new()
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct IntegerLiteral {
+    pub token: Token,
+    pub value: i64,
+}
+
+impl Node for IntegerLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        self.token_literal()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct StringLiteral {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Node for StringLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let mut output = String::new();
+        output.push('"');
+        for ch in self.value.chars() {
+            match ch {
+                '\n' => output.push_str("\\n"),
+                '\t' => output.push_str("\\t"),
+                '\r' => output.push_str("\\r"),
+                '"' => output.push_str("\\\""),
+                '\\' => output.push_str("\\\\"),
+                ch if ch.is_control() => output.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+                ch => output.push(ch),
+            }
         }
-        output.push_str(";");
+        output.push('"');
         output
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone)]
+pub struct PrefixExpression {
+    pub token: Token,
+    pub operator: String,
+    pub right: Box<ExpressionNode>,
+}
+
+impl Node for PrefixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        format!("({}{})", self.operator, self.right.print())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InfixExpression {
+    pub token: Token,
+    pub left: Box<ExpressionNode>,
+    pub operator: String,
+    pub right: Box<ExpressionNode>,
+}
+
+impl Node for InfixExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        format!("({} {} {})", self.left.print(), self.operator, self.right.print())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BooleanLiteral {
+    pub token: Token,
+    pub value: bool,
+}
+
+impl Node for BooleanLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        self.token_literal()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IfExpression {
+    pub token: Token,
+    pub condition: Box<ExpressionNode>,
+    pub consequence: BlockStatement,
+    pub alternative: Option<BlockStatement>,
+}
+
+impl Node for IfExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let mut output = String::new();
+        output.push_str("if (");
+        output.push_str(&self.condition.print());
+        output.push_str(") ");
+        output.push_str(&self.consequence.print());
+        if let Some(alternative) = &self.alternative {
+            output.push_str(" else ");
+            output.push_str(&alternative.print());
+        }
+        output
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BlockStatement {
+    pub token: Token,
+    pub statements: Vec<StatementNode>,
+}
+
+impl Node for BlockStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let mut output = String::new();
+        output.push('{');
+        for statement in &self.statements {
+            output.push(' ');
+            output.push_str(&statement.print());
+            // expression statements print without their terminator, so keep
+            // neighbours from running together when the output is re-parsed
+            if let StatementNode::Expression(_) = statement {
+                output.push(';');
+            }
+        }
+        output.push_str(" }");
+        output
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionLiteral {
+    pub token: Token,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+impl Node for FunctionLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.print()).collect();
+        format!("{}({}) {}", self.token_literal(), params.join(", "), self.body.print())
+    }
+}
+
+/// `macro(x, y) { ... }`, which only means something as the value of a
+/// top-level `let`: the macro is expanded away before the program runs.
+#[derive(Debug, Clone)]
+pub struct MacroLiteral {
+    pub token: Token,
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+impl Node for MacroLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.print()).collect();
+        format!("{}({}) {}", self.token_literal(), params.join(", "), self.body.print())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallExpression {
+    pub token: Token,
+    pub function: Box<ExpressionNode>,
+    pub arguments: Vec<ExpressionNode>,
+}
+
+impl Node for CallExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let args: Vec<String> = self.arguments.iter().map(|a| a.print()).collect();
+        format!("{}({})", self.function.print(), args.join(", "))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ArrayLiteral {
+    pub token: Token,
+    pub elements: Vec<ExpressionNode>,
+}
+
+impl Node for ArrayLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let elements: Vec<String> = self.elements.iter().map(|e| e.print()).collect();
+        format!("[{}]", elements.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexExpression {
+    pub token: Token,
+    pub left: Box<ExpressionNode>,
+    pub index: Box<ExpressionNode>,
+}
+
+impl Node for IndexExpression {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        format!("({}[{}])", self.left.print(), self.index.print())
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct HashLiteral {
+    pub token: Token,
+    // kept in source order, duplicates and all; the evaluator decides which wins
+    pub pairs: Vec<(ExpressionNode, ExpressionNode)>,
+}
+
+impl Node for HashLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal.clone()
+    }
+
+    fn print(&self) -> String {
+        let pairs: Vec<String> = self.pairs.iter()
+            .map(|(key, value)| format!("{}: {}", key.print(), value.print()))
+            .collect();
+        format!("{{{}}}", pairs.join(", "))
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::object::{Builtin, Object, RuntimeError};
+
+/// The native functions a program can call by name. Identifiers are looked
+/// up here when no binding in the environment matches, so a `let` can shadow
+/// any of them.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    functions: HashMap<String, Builtin>,
+}
+
+impl Registry {
+    /// A registry holding the builtins every Monkey program can rely on.
+    pub fn standard() -> Self {
+        let mut registry = Registry { functions: HashMap::new() };
+
+        registry.register("len", len);
+        registry.register("puts", puts);
+        registry.register("type", type_of);
+        registry.register("str", str);
+        registry.register("int", int);
+        registry.register("range", range);
+        registry.register("first", first);
+        registry.register("last", last);
+        registry.register("rest", rest);
+        registry.register("push", push);
+
+        registry
+    }
+
+    /// Makes `func` callable as `name`, replacing any builtin already
+    /// registered under that name.
+    pub fn register<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn(&[Object]) -> Result<Object, RuntimeError> + 'static,
+    {
+        self.functions.insert(name.to_string(), Builtin::new(name, func));
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<Object> {
+        self.functions.get(name).cloned().map(Object::Builtin)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::standard()
+    }
+}
+
+/// An error raised by a builtin. The evaluator points it at the call that
+/// failed, so no span is needed here.
+fn error(message: String) -> RuntimeError {
+    RuntimeError::new(Default::default(), message)
+}
+
+fn check_arity(args: &[Object], want: usize) -> Result<(), RuntimeError> {
+    if args.len() != want {
+        return Err(error(format!("wrong number of arguments. got={}, want={}", args.len(), want)));
+    }
+    Ok(())
+}
+
+fn array_arg<'a>(name: &str, arg: &'a Object) -> Result<&'a Rc<Vec<Object>>, RuntimeError> {
+    match arg {
+        Object::Array(elements) => Ok(elements),
+        other => Err(error(format!("argument to `{}` must be ARRAY, got {}", name, other.type_name()))),
+    }
+}
+
+fn len(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+
+    match &args[0] {
+        Object::String(value) => Ok(Object::Integer(value.chars().count() as i64)),
+        Object::Array(elements) => Ok(Object::Integer(elements.len() as i64)),
+        Object::Hash(pairs) => Ok(Object::Integer(pairs.len() as i64)),
+        other => Err(error(format!("argument to `len` not supported, got {}", other.type_name()))),
+    }
+}
+
+fn puts(args: &[Object]) -> Result<Object, RuntimeError> {
+    for arg in args {
+        println!("{}", arg.inspect());
+    }
+
+    Ok(Object::Null)
+}
+
+fn type_of(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+
+    Ok(Object::String(args[0].type_name().to_string()))
+}
+
+fn str(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+
+    Ok(Object::String(args[0].inspect()))
+}
+
+fn int(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+
+    match &args[0] {
+        Object::Integer(value) => Ok(Object::Integer(*value)),
+        Object::Boolean(value) => Ok(Object::Integer(*value as i64)),
+        Object::String(value) => value.trim().parse()
+            .map(Object::Integer)
+            .map_err(|_| error(format!("could not parse {:?} as an integer", value))),
+        other => Err(error(format!("argument to `int` not supported, got {}", other.type_name()))),
+    }
+}
+
+// the longest array `range` builds, which keeps a typo from exhausting memory
+const MAX_RANGE_LEN: i64 = 1 << 24;
+
+/// `range(end)` or `range(start, end)`: the integers from `start` (or 0) up
+/// to but not including `end`.
+fn range(args: &[Object]) -> Result<Object, RuntimeError> {
+    let bounds: Vec<i64> = args.iter()
+        .map(|arg| match arg {
+            Object::Integer(value) => Ok(*value),
+            other => Err(error(format!("argument to `range` must be INTEGER, got {}", other.type_name()))),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let (start, end) = match bounds.as_slice() {
+        [end] => (0, *end),
+        [start, end] => (*start, *end),
+        _ => return Err(error(format!("wrong number of arguments. got={}, want=1 or 2", args.len()))),
+    };
+
+    match end.checked_sub(start) {
+        Some(len) if len <= MAX_RANGE_LEN => Ok(Object::Array(Rc::new((start..end).map(Object::Integer).collect()))),
+        _ => Err(error(format!("range too long (the limit is {} elements)", MAX_RANGE_LEN))),
+    }
+}
+
+fn first(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+    let elements = array_arg("first", &args[0])?;
+
+    Ok(elements.first().cloned().unwrap_or(Object::Null))
+}
+
+fn last(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+    let elements = array_arg("last", &args[0])?;
+
+    Ok(elements.last().cloned().unwrap_or(Object::Null))
+}
+
+fn rest(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 1)?;
+    let elements = array_arg("rest", &args[0])?;
+
+    if elements.is_empty() {
+        return Ok(Object::Null);
+    }
+    Ok(Object::Array(Rc::new(elements[1..].to_vec())))
+}
+
+fn push(args: &[Object]) -> Result<Object, RuntimeError> {
+    check_arity(args, 2)?;
+    let elements = array_arg("push", &args[0])?;
+
+    let mut pushed = Vec::with_capacity(elements.len() + 1);
+    pushed.extend(elements.iter().cloned());
+    pushed.push(args[1].clone());
+    Ok(Object::Array(Rc::new(pushed)))
+}
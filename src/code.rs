@@ -0,0 +1,311 @@
+use std::fmt;
+use crate::token::Span;
+
+/// A sequence of encoded instructions: an opcode byte followed by its
+/// operands, each stored big-endian in the width its definition gives.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Instructions(pub Vec<u8>);
+
+#[repr(u8)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Opcode {
+    Constant,
+    Pop,
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+
+    True,
+    False,
+    Null,
+
+    Equal,
+    NotEqual,
+    LessThan,
+    GreaterThan,
+
+    Minus,
+    Bang,
+
+    JumpNotTruthy,
+    Jump,
+
+    GetGlobal,
+    SetGlobal,
+    GetLocal,
+    SetLocal,
+    GetFree,
+    CurrentClosure,
+
+    Array,
+    Hash,
+    Index,
+
+    Call,
+    ReturnValue,
+    Return,
+    Closure,
+}
+
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+const OPCODES: [Opcode; 30] = [
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Null,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::LessThan,
+    Opcode::GreaterThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetFree,
+    Opcode::CurrentClosure,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::Closure,
+];
+
+impl Opcode {
+    pub fn from_byte(byte: u8) -> Option<Opcode> {
+        OPCODES.get(byte as usize).copied()
+    }
+
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            // argument count
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            // constant index of the function, number of free variables
+            Opcode::Closure => ("OpClosure", &[2, 1]),
+        };
+
+        Definition { name, operand_widths }
+    }
+}
+
+/// Encodes one instruction. Panics if `operands` doesn't match the opcode's
+/// definition or an operand doesn't fit its width; the compiler checks
+/// limits before getting here.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+    let definition = op.definition();
+    assert_eq!(operands.len(), definition.operand_widths.len(), "wrong number of operands for {}", definition.name);
+
+    let mut instruction = vec![op as u8];
+    for (&operand, &width) in operands.iter().zip(definition.operand_widths) {
+        assert!(operand < 1 << (width * 8), "operand {} of {} doesn't fit in {} bytes", operand, definition.name, width);
+        instruction.extend_from_slice(&operand.to_be_bytes()[size_of::<usize>() - width..]);
+    }
+
+    instruction
+}
+
+/// Decodes the operands following an opcode, returning them along with the
+/// number of bytes they took up.
+pub fn read_operands(definition: &Definition, bytes: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(definition.operand_widths.len());
+    let mut offset = 0;
+
+    for &width in definition.operand_widths {
+        operands.push(read_operand(&bytes[offset..], width));
+        offset += width;
+    }
+
+    (operands, offset)
+}
+
+pub fn read_operand(bytes: &[u8], width: usize) -> usize {
+    bytes[..width].iter().fold(0, |value, &byte| value << 8 | byte as usize)
+}
+
+/// Where in the source the instructions that can fail at runtime came from,
+/// so the VM can point its errors at the same place the evaluator would.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SourceMap(Vec<(usize, Span)>);
+
+impl SourceMap {
+    /// Records the span of the instruction at `offset`. Offsets must be
+    /// added in increasing order.
+    pub fn add(&mut self, offset: usize, span: Span) {
+        self.0.push((offset, span));
+    }
+
+    pub fn get(&self, offset: usize) -> Option<Span> {
+        self.0.binary_search_by_key(&offset, |(offset, _)| *offset)
+            .ok()
+            .map(|i| self.0[i].1)
+    }
+}
+
+impl Instructions {
+    /// Disassembles the instructions, one per line, each prefixed with its
+    /// byte offset and followed by its source position if `source_map` has
+    /// one:
+    ///
+    /// ```text
+    /// 0000 OpConstant 0
+    /// 0003 OpMinus (1:1)
+    /// ```
+    pub fn disassemble(&self, source_map: &SourceMap) -> String {
+        let mut output = String::new();
+        let mut offset = 0;
+
+        while offset < self.0.len() {
+            let op = match Opcode::from_byte(self.0[offset]) {
+                Some(op) => op,
+                None => {
+                    output.push_str(&format!("{:04} ERROR: unknown opcode {}\n", offset, self.0[offset]));
+                    offset += 1;
+                    continue;
+                }
+            };
+
+            let definition = op.definition();
+            let (operands, read) = read_operands(&definition, &self.0[offset + 1..]);
+
+            output.push_str(&format!("{:04} {}", offset, definition.name));
+            for operand in operands {
+                output.push_str(&format!(" {}", operand));
+            }
+            if let Some(span) = source_map.get(offset) {
+                output.push_str(&format!(" ({})", span.start));
+            }
+            output.push('\n');
+
+            offset += 1 + read;
+        }
+
+        output
+    }
+}
+
+impl fmt::Display for Instructions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.disassemble(&SourceMap::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_make() {
+        let tests = [
+            (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+            (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+            (Opcode::GetLocal, vec![255], vec![Opcode::GetLocal as u8, 255]),
+            (Opcode::Closure, vec![65534, 255], vec![Opcode::Closure as u8, 255, 254, 255]),
+        ];
+
+        for (op, operands, expected) in tests {
+            assert_eq!(make(op, &operands), expected, "wrong encoding for {:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_opcodes_round_trip() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as u8, byte as u8, "{:?} is out of place in OPCODES", op);
+            assert_eq!(Opcode::from_byte(byte as u8), Some(*op));
+        }
+        assert_eq!(Opcode::from_byte(OPCODES.len() as u8), None);
+    }
+
+    #[test]
+    fn test_read_operands() {
+        let tests = [
+            (Opcode::Constant, vec![65535], 2),
+            (Opcode::GetLocal, vec![255], 1),
+            (Opcode::Closure, vec![65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests {
+            let instruction = make(op, &operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+
+            assert_eq!(n, bytes_read, "wrong number of bytes read for {:?}", op);
+            assert_eq!(read, operands, "wrong operands for {:?}", op);
+        }
+    }
+
+    #[test]
+    fn test_instructions_display() {
+        let instructions = Instructions([
+            make(Opcode::Add, &[]),
+            make(Opcode::GetLocal, &[1]),
+            make(Opcode::Constant, &[2]),
+            make(Opcode::Constant, &[65535]),
+            make(Opcode::Closure, &[65535, 255]),
+        ].concat());
+
+        assert_eq!(instructions.to_string(), concat!(
+            "0000 OpAdd\n",
+            "0001 OpGetLocal 1\n",
+            "0003 OpConstant 2\n",
+            "0006 OpConstant 65535\n",
+            "0009 OpClosure 65535 255\n",
+        ));
+    }
+
+    #[test]
+    fn test_disassemble_with_source_map() {
+        let instructions = Instructions([make(Opcode::True, &[]), make(Opcode::Minus, &[])].concat());
+        let mut source_map = SourceMap::default();
+        source_map.add(1, Span {
+            start: crate::token::Position { line: 2, column: 5, offset: 9 },
+            end: crate::token::Position { line: 2, column: 6, offset: 10 },
+        });
+
+        assert_eq!(source_map.get(0), None);
+        assert_eq!(instructions.disassemble(&source_map), "0000 OpTrue\n0001 OpMinus (2:5)\n");
+    }
+}
@@ -0,0 +1,799 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::ast::{BlockStatement, ExpressionNode, ExpressionStatement, FunctionLiteral, Node, Program, StatementNode};
+use crate::code::{self, Instructions, Opcode, SourceMap};
+use crate::macros;
+use crate::object::{CompiledFunction, Object};
+use crate::symbol_table::{Symbol, SymbolScope, SymbolTable};
+use crate::token::Span;
+
+const MAX_CONSTANTS: usize = u16::MAX as usize + 1;
+const MAX_GLOBALS: usize = u16::MAX as usize + 1;
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+const MAX_ARGUMENTS: usize = u8::MAX as usize;
+const MAX_ELEMENTS: usize = u16::MAX as usize;
+const MAX_JUMP: usize = u16::MAX as usize;
+
+/// What the compiler hands the VM: the program's own instructions, the
+/// constants they refer to, and the names of the global slots.
+#[derive(Debug, Clone)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub source_map: SourceMap,
+    pub constants: Vec<Object>,
+    pub globals: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl CompileError {
+    fn new(span: Span, message: String) -> Self {
+        CompileError { message, span }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+#[derive(Default)]
+struct CompilationScope {
+    instructions: Vec<u8>,
+    source_map: SourceMap,
+    last_instruction: Option<(Opcode, usize)>,
+}
+
+/// Compiles programs to bytecode. Constants and global names are kept from
+/// one program to the next, so a REPL can compile line by line against the
+/// same globals.
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            constants: vec![],
+            symbol_table: SymbolTable::new(),
+            scopes: vec![CompilationScope::default()],
+        }
+    }
+
+    /// Compiles `program` so that running it leaves the value of its last
+    /// statement, as the evaluator would return it.
+    pub fn compile(&mut self, program: &Program) -> Result<Bytecode, CompileError> {
+        if let Err(err) = self.compile_block(&program.statements) {
+            // throw away the half-compiled program; function scopes have
+            // already been left on the way out
+            self.scopes[0] = CompilationScope::default();
+            return Err(err);
+        }
+
+        let scope = std::mem::take(&mut self.scopes[0]);
+        Ok(Bytecode {
+            instructions: Instructions(scope.instructions),
+            source_map: scope.source_map,
+            constants: self.constants.clone(),
+            globals: self.symbol_table.global_names(),
+        })
+    }
+
+    fn compile_statement(&mut self, statement: &StatementNode) -> Result<(), CompileError> {
+        match statement {
+            StatementNode::Let(stmt) => {
+                match &stmt.value {
+                    // a global function's name is looked up when it's called,
+                    // like any other global
+                    Some(ExpressionNode::Function(func)) if self.scopes.len() > 1 => {
+                        self.compile_function(func, Some(&stmt.name.value))?
+                    }
+                    Some(value) => self.compile_expression(value)?,
+                    None => {
+                        self.emit(Opcode::Null, &[]);
+                    }
+                }
+
+                let symbol = self.symbol_table.define(&stmt.name.value);
+                self.check_slots(&symbol, stmt.name.token.span)?;
+                match symbol.scope {
+                    SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index]),
+                    _ => self.emit(Opcode::SetLocal, &[symbol.index]),
+                };
+            }
+            StatementNode::Return(stmt) => {
+                match &stmt.return_value {
+                    Some(value) => self.compile_expression(value)?,
+                    None => {
+                        self.emit(Opcode::Null, &[]);
+                    }
+                }
+                self.emit(Opcode::ReturnValue, &[]);
+            }
+            StatementNode::Expression(stmt) => {
+                if let Some(expression) = &stmt.expression {
+                    self.compile_expression(expression)?;
+                    self.emit(Opcode::Pop, &[]);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles statements whose value is used, such as a branch of an `if`:
+    /// the last one's value is left on the stack, or null if it has none.
+    fn compile_block(&mut self, statements: &[StatementNode]) -> Result<(), CompileError> {
+        for statement in statements {
+            self.compile_statement(statement)?;
+        }
+
+        match statements.last() {
+            Some(StatementNode::Expression(ExpressionStatement { expression: Some(_), .. })) => {
+                self.remove_last_pop();
+            }
+            _ => {
+                self.emit(Opcode::Null, &[]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &ExpressionNode) -> Result<(), CompileError> {
+        match expression {
+            ExpressionNode::Integer(lit) => {
+                let index = self.add_constant(Object::Integer(lit.value), lit.token.span)?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            ExpressionNode::String(lit) => {
+                let index = self.add_constant(Object::String(lit.value.clone()), lit.token.span)?;
+                self.emit(Opcode::Constant, &[index]);
+            }
+            ExpressionNode::Boolean(lit) => {
+                self.emit(if lit.value { Opcode::True } else { Opcode::False }, &[]);
+            }
+            ExpressionNode::Prefix(exp) => {
+                self.compile_expression(&exp.right)?;
+
+                let op = match exp.operator.as_str() {
+                    "!" => Opcode::Bang,
+                    "-" => Opcode::Minus,
+                    other => return Err(CompileError::new(exp.token.span, format!("unknown operator: {}", other))),
+                };
+                self.emit_spanned(exp.token.span, op, &[]);
+            }
+            ExpressionNode::Infix(exp) => {
+                self.compile_expression(&exp.left)?;
+                self.compile_expression(&exp.right)?;
+
+                let op = match exp.operator.as_str() {
+                    "+" => Opcode::Add,
+                    "-" => Opcode::Sub,
+                    "*" => Opcode::Mul,
+                    "/" => Opcode::Div,
+                    "==" => Opcode::Equal,
+                    "!=" => Opcode::NotEqual,
+                    "<" => Opcode::LessThan,
+                    ">" => Opcode::GreaterThan,
+                    other => return Err(CompileError::new(exp.token.span, format!("unknown operator: {}", other))),
+                };
+                self.emit_spanned(exp.token.span, op, &[]);
+            }
+            ExpressionNode::If(exp) => {
+                self.compile_expression(&exp.condition)?;
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0]);
+
+                self.compile_block(&exp.consequence.statements)?;
+                let jump = self.emit(Opcode::Jump, &[0]);
+
+                self.patch_jump(jump_not_truthy, exp.token.span)?;
+                match &exp.alternative {
+                    Some(BlockStatement { statements, .. }) => self.compile_block(statements)?,
+                    None => {
+                        self.emit(Opcode::Null, &[]);
+                    }
+                }
+                self.patch_jump(jump, exp.token.span)?;
+            }
+            ExpressionNode::IdentifierNode(ident) => {
+                let symbol = match self.symbol_table.resolve(&ident.value) {
+                    Some(symbol) => symbol,
+                    None => {
+                        let symbol = self.symbol_table.define_global(&ident.value);
+                        self.check_slots(&symbol, ident.token.span)?;
+                        symbol
+                    }
+                };
+                self.load_symbol(&symbol, ident.token.span);
+            }
+            ExpressionNode::Array(array) => {
+                check_limit(array.elements.len(), MAX_ELEMENTS, "array elements", array.token.span)?;
+                for element in &array.elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::Array, &[array.elements.len()]);
+            }
+            ExpressionNode::Hash(hash) => {
+                check_limit(hash.pairs.len() * 2, MAX_ELEMENTS, "hash pairs", hash.token.span)?;
+                for (key, value) in &hash.pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                self.emit_spanned(hash.token.span, Opcode::Hash, &[hash.pairs.len() * 2]);
+            }
+            ExpressionNode::Index(exp) => {
+                self.compile_expression(&exp.left)?;
+                self.compile_expression(&exp.index)?;
+                self.emit_spanned(exp.token.span, Opcode::Index, &[]);
+            }
+            ExpressionNode::Function(func) => self.compile_function(func, None)?,
+            ExpressionNode::Macro(mac) => {
+                return Err(CompileError::new(mac.token.span, macros::MISPLACED_MACRO.to_string()));
+            }
+            ExpressionNode::Call(call) => {
+                // quoted code can't be built without the evaluator; macros
+                // still work, having been expanded before compilation
+                if macros::is_call_to(call, "quote") {
+                    return Err(CompileError::new(call.token.span, "quote is only supported by the eval engine".to_string()));
+                }
+                check_limit(call.arguments.len(), MAX_ARGUMENTS, "arguments", call.token.span)?;
+                self.compile_expression(&call.function)?;
+                for argument in &call.arguments {
+                    self.compile_expression(argument)?;
+                }
+                self.emit_spanned(call.token.span, Opcode::Call, &[call.arguments.len()]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles a function literal into a constant and the instruction that
+    /// turns it into a closure. `name` is what a `let` inside a function binds
+    /// it to, so the function can call itself without capturing its own
+    /// closure.
+    fn compile_function(&mut self, func: &FunctionLiteral, name: Option<&str>) -> Result<(), CompileError> {
+        self.enter_scope();
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for parameter in &func.parameters {
+            let symbol = self.symbol_table.define(&parameter.value);
+            if let Err(err) = self.check_slots(&symbol, parameter.token.span) {
+                self.leave_scope();
+                return Err(err);
+            }
+        }
+
+        if let Err(err) = self.compile_block(&func.body.statements) {
+            self.leave_scope();
+            return Err(err);
+        }
+        self.emit(Opcode::ReturnValue, &[]);
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions.max(func.parameters.len());
+        let (instructions, source_map) = self.leave_scope();
+
+        check_limit(free_symbols.len(), MAX_LOCALS - 1, "captured variables", func.token.span)?;
+        for symbol in &free_symbols {
+            self.load_symbol(symbol, func.token.span);
+        }
+
+        let compiled = CompiledFunction {
+            instructions,
+            source_map,
+            num_locals,
+            num_parameters: func.parameters.len(),
+            source: func.print(),
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(compiled)), func.token.span)?;
+        self.emit(Opcode::Closure, &[index, free_symbols.len()]);
+
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol, span: Span) {
+        match symbol.scope {
+            // the only load that can fail: the global may not be set yet
+            SymbolScope::Global => self.emit_spanned(span, Opcode::GetGlobal, &[symbol.index]),
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+        };
+    }
+
+    fn check_slots(&self, symbol: &Symbol, span: Span) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::Global => check_limit(symbol.index + 1, MAX_GLOBALS, "global variables", span),
+            _ => check_limit(symbol.index + 1, MAX_LOCALS, "local variables", span),
+        }
+    }
+
+    fn add_constant(&mut self, object: Object, span: Span) -> Result<usize, CompileError> {
+        check_limit(self.constants.len() + 1, MAX_CONSTANTS, "constants", span)?;
+        self.constants.push(object);
+        Ok(self.constants.len() - 1)
+    }
+
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+        let scope = self.scope();
+        let position = scope.instructions.len();
+
+        scope.instructions.extend(code::make(op, operands));
+        scope.last_instruction = Some((op, position));
+        position
+    }
+
+    /// Emits an instruction that can fail at runtime, remembering `span` so
+    /// the error points at the code it came from.
+    fn emit_spanned(&mut self, span: Span, op: Opcode, operands: &[usize]) -> usize {
+        let position = self.emit(op, operands);
+        self.scope().source_map.add(position, span);
+        position
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.scope();
+        if let Some((Opcode::Pop, position)) = scope.last_instruction {
+            scope.instructions.truncate(position);
+            scope.last_instruction = None;
+        }
+    }
+
+    /// Points the jump at `position` to the next instruction to be emitted.
+    fn patch_jump(&mut self, position: usize, span: Span) -> Result<(), CompileError> {
+        let scope = self.scope();
+        let target = scope.instructions.len();
+        check_limit(target, MAX_JUMP, "instructions in one function", span)?;
+
+        let op = Opcode::from_byte(scope.instructions[position]).expect("should be patching a jump");
+        let instruction = code::make(op, &[target]);
+        scope.instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+        Ok(())
+    }
+
+    fn scope(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("there is always a main scope")
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        self.symbol_table = SymbolTable::new_enclosed(std::mem::take(&mut self.symbol_table));
+    }
+
+    fn leave_scope(&mut self) -> (Instructions, SourceMap) {
+        let scope = self.scopes.pop().expect("should be leaving a function scope");
+        self.symbol_table = std::mem::take(&mut self.symbol_table)
+            .into_outer()
+            .expect("function scopes have an outer symbol table");
+
+        (Instructions(scope.instructions), scope.source_map)
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new()
+    }
+}
+
+fn check_limit(count: usize, max: usize, what: &str, span: Span) -> Result<(), CompileError> {
+    if count > max {
+        return Err(CompileError::new(span, format!("too many {} (the limit is {})", what, max)));
+    }
+    Ok(())
+}
+
+/// Disassembles the program and then every function in its constant pool,
+/// with the global slots listed up front.
+impl fmt::Display for Bytecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.globals.is_empty() {
+            writeln!(f, "globals: {}", self.globals.join(", "))?;
+            writeln!(f)?;
+        }
+        write!(f, "{}", self.instructions.disassemble(&self.source_map))?;
+
+        for (index, constant) in self.constants.iter().enumerate() {
+            if let Object::CompiledFunction(func) = constant {
+                writeln!(f)?;
+                writeln!(f, "constant {} (fn, {} parameters, {} locals):", index, func.num_parameters, func.num_locals)?;
+                write!(f, "{}", func.instructions.disassemble(&func.source_map))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::make;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    enum Constant {
+        Integer(i64),
+        String(&'static str),
+        Function(Vec<Vec<u8>>),
+    }
+
+    fn compile(input: &str) -> Bytecode {
+        let program = Parser::new(Lexer::new(input)).parse_program().expect("input should parse");
+        Compiler::new().compile(&program).expect("input should compile")
+    }
+
+    // input, expected constants, expected instructions
+    type CompilerTest = (&'static str, Vec<Constant>, Vec<Vec<u8>>);
+
+    fn run_compiler_tests(tests: Vec<CompilerTest>) {
+        for (input, constants, instructions) in tests {
+            let bytecode = compile(input);
+
+            assert_eq!(
+                bytecode.instructions.to_string(),
+                Instructions(instructions.concat()).to_string(),
+                "wrong instructions for {}", input
+            );
+            assert_eq!(bytecode.constants.len(), constants.len(), "wrong number of constants for {}", input);
+
+            for (actual, expected) in bytecode.constants.iter().zip(constants) {
+                match (actual, expected) {
+                    (Object::Integer(actual), Constant::Integer(expected)) => assert_eq!(*actual, expected),
+                    (Object::String(actual), Constant::String(expected)) => assert_eq!(actual, expected),
+                    (Object::CompiledFunction(func), Constant::Function(expected)) => assert_eq!(
+                        func.instructions.to_string(),
+                        Instructions(expected.concat()).to_string(),
+                        "wrong function instructions for {}", input
+                    ),
+                    (actual, _) => panic!("unexpected constant {:?} for {}", actual, input),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        run_compiler_tests(vec![
+            ("1 + 2", vec![Constant::Integer(1), Constant::Integer(2)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+            ]),
+            ("1; 2", vec![Constant::Integer(1), Constant::Integer(2)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[1]),
+            ]),
+            ("2 / 1 - 1 * 3", vec![Constant::Integer(2), Constant::Integer(1), Constant::Integer(1), Constant::Integer(3)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Div, &[]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Constant, &[3]),
+                make(Opcode::Mul, &[]),
+                make(Opcode::Sub, &[]),
+            ]),
+            ("-1", vec![Constant::Integer(1)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Minus, &[]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        run_compiler_tests(vec![
+            ("true", vec![], vec![make(Opcode::True, &[])]),
+            ("!false", vec![], vec![make(Opcode::False, &[]), make(Opcode::Bang, &[])]),
+            // `<` keeps its operands in order, so errors read the same as in the evaluator
+            ("1 < 2", vec![Constant::Integer(1), Constant::Integer(2)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::LessThan, &[]),
+            ]),
+            ("true != false", vec![], vec![
+                make(Opcode::True, &[]),
+                make(Opcode::False, &[]),
+                make(Opcode::NotEqual, &[]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        run_compiler_tests(vec![
+            ("if (true) { 10 }; 3333;", vec![Constant::Integer(10), Constant::Integer(3333)], vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[10]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Jump, &[11]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[1]),
+            ]),
+            ("if (true) { 10 } else { 20 }", vec![Constant::Integer(10), Constant::Integer(20)], vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[10]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Jump, &[13]),
+                make(Opcode::Constant, &[1]),
+            ]),
+            // a block ending in a `let` is worth null, like in the evaluator
+            ("if (true) { let x = 1; }", vec![Constant::Integer(1)], vec![
+                make(Opcode::True, &[]),
+                make(Opcode::JumpNotTruthy, &[14]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+                make(Opcode::Jump, &[15]),
+                make(Opcode::Null, &[]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        run_compiler_tests(vec![
+            ("let one = 1; let two = 2;", vec![Constant::Integer(1), Constant::Integer(2)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::Null, &[]),
+            ]),
+            ("let one = 1; one", vec![Constant::Integer(1)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+            ]),
+            ("let one = 1; let one = one;", vec![Constant::Integer(1)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::Null, &[]),
+            ]),
+            // builtins and globals defined later are looked up when the code runs
+            ("len; later", vec![], vec![
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::GetGlobal, &[1]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_strings_arrays_hashes_and_indexes() {
+        run_compiler_tests(vec![
+            (r#""mon" + "key""#, vec![Constant::String("mon"), Constant::String("key")], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+            ]),
+            ("[]", vec![], vec![make(Opcode::Array, &[0])]),
+            ("[1, 2][0]", vec![Constant::Integer(1), Constant::Integer(2), Constant::Integer(0)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Array, &[2]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Index, &[]),
+            ]),
+            (r#"{"b": 2, "a": 1}"#, vec![Constant::String("b"), Constant::Integer(2), Constant::String("a"), Constant::Integer(1)], vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Constant, &[3]),
+                make(Opcode::Hash, &[4]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_functions_and_calls() {
+        run_compiler_tests(vec![
+            ("fn() { return 5 + 10 }", vec![Constant::Integer(5), Constant::Integer(10), Constant::Function(vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+                make(Opcode::Null, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])], vec![
+                make(Opcode::Closure, &[2, 0]),
+            ]),
+            ("fn() { 1; 2 }", vec![Constant::Integer(1), Constant::Integer(2), Constant::Function(vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ])], vec![
+                make(Opcode::Closure, &[2, 0]),
+            ]),
+            ("fn() { }", vec![Constant::Function(vec![
+                make(Opcode::Null, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ])], vec![
+                make(Opcode::Closure, &[0, 0]),
+            ]),
+            ("let f = fn(a, b) { let c = a; c + b }; f(1, 2)", vec![Constant::Function(vec![
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::SetLocal, &[2]),
+                make(Opcode::GetLocal, &[2]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ]), Constant::Integer(1), Constant::Integer(2)], vec![
+                make(Opcode::Closure, &[0, 0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[2]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_closures() {
+        run_compiler_tests(vec![
+            ("fn(a) { fn(b) { fn(c) { a + b + c } } }", vec![
+                Constant::Function(vec![
+                    make(Opcode::GetFree, &[0]),
+                    make(Opcode::GetFree, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                Constant::Function(vec![
+                    make(Opcode::GetFree, &[0]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Closure, &[0, 2]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                Constant::Function(vec![
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Closure, &[1, 1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+            ], vec![
+                make(Opcode::Closure, &[2, 0]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        run_compiler_tests(vec![
+            ("let countDown = fn(x) { countDown(x - 1) }; countDown(1)", vec![
+                Constant::Integer(1),
+                Constant::Function(vec![
+                    make(Opcode::GetGlobal, &[0]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Sub, &[]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                Constant::Integer(1),
+            ], vec![
+                make(Opcode::Closure, &[1, 0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Call, &[1]),
+            ]),
+            ("let wrapper = fn() { let countDown = fn(x) { countDown(x - 1) }; countDown(1) }; wrapper()", vec![
+                Constant::Integer(1),
+                Constant::Function(vec![
+                    make(Opcode::CurrentClosure, &[]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Sub, &[]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+                Constant::Integer(1),
+                Constant::Function(vec![
+                    make(Opcode::Closure, &[1, 0]),
+                    make(Opcode::SetLocal, &[0]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ]),
+            ], vec![
+                make(Opcode::Closure, &[3, 0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::Call, &[0]),
+            ]),
+        ]);
+    }
+
+    #[test]
+    fn test_source_map_points_at_failing_instructions() {
+        let bytecode = compile("let x = 1;\nx + true");
+        let add = bytecode.instructions.0.iter().position(|&b| b == Opcode::Add as u8).unwrap();
+
+        assert_eq!(bytecode.source_map.get(add).map(|span| span.start.to_string()), Some("2:3".to_string()));
+        assert_eq!(bytecode.source_map.get(0), None);
+    }
+
+    #[test]
+    fn test_globals_persist_between_programs() {
+        let mut compiler = Compiler::new();
+        let first = Parser::new(Lexer::new("let a = 1;")).parse_program().unwrap();
+        let second = Parser::new(Lexer::new("let b = 2; a + b")).parse_program().unwrap();
+
+        compiler.compile(&first).unwrap();
+        let bytecode = compiler.compile(&second).unwrap();
+
+        assert_eq!(bytecode.globals, ["a", "b"]);
+        assert_eq!(bytecode.constants.len(), 2);
+        assert_eq!(bytecode.instructions.to_string(), concat!(
+            "0000 OpConstant 1\n",
+            "0003 OpSetGlobal 1\n",
+            "0006 OpGetGlobal 0\n",
+            "0009 OpGetGlobal 1\n",
+            "0012 OpAdd\n",
+        ));
+    }
+
+    #[test]
+    fn test_too_many_locals() {
+        let params: Vec<String> = (0..300).map(|i| format!("p{i}")).collect();
+        let input = format!("fn({}) {{ }}", params.join(", "));
+        let program = Parser::new(Lexer::new(&input)).parse_program().unwrap();
+
+        let err = Compiler::new().compile(&program).unwrap_err();
+        assert_eq!(err.message, "too many local variables (the limit is 256)");
+    }
+
+    #[test]
+    fn test_code_only_the_evaluator_handles() {
+        let tests = [
+            ("let a = 1;\nquote(a)", "quote is only supported by the eval engine", "2:6"),
+            ("[macro(x) { x }]", "macros can only be defined by a top-level let", "1:2"),
+        ];
+
+        for (input, message, position) in tests {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            let err = Compiler::new().compile(&program).unwrap_err();
+
+            assert_eq!(err.message, message, "wrong error message for {}", input);
+            assert_eq!(err.span.start.to_string(), position, "wrong error position for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_bytecode_display() {
+        assert_eq!(compile("let f = fn(x) { -x }; f(1)").to_string(), concat!(
+            "globals: f\n",
+            "\n",
+            "0000 OpClosure 0 0\n",
+            "0004 OpSetGlobal 0\n",
+            "0007 OpGetGlobal 0 (1:23)\n",
+            "0010 OpConstant 1\n",
+            "0013 OpCall 1 (1:24)\n",
+            "\n",
+            "constant 0 (fn, 1 parameters, 1 locals):\n",
+            "0000 OpGetLocal 0\n",
+            "0002 OpMinus (1:17)\n",
+            "0003 OpReturnValue\n",
+        ));
+    }
+}
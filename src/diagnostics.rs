@@ -0,0 +1,251 @@
+use crate::compiler::CompileError;
+use crate::lexer::LexError;
+use crate::object::RuntimeError;
+use crate::parser::ParseError;
+use crate::token::Span;
+use crate::unicode;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// An error ready to be shown to a user: a headline, the source range it is
+/// about, and optional extra lines explaining what went wrong.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub label: Option<String>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: String, span: Span) -> Self {
+        Diagnostic { code, message, span, label: None, notes: vec![], help: None }
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Self {
+        let diagnostic = Diagnostic::error(err.code(), err.to_string(), err.span());
+
+        match err {
+            ParseError::UnexpectedToken { expected, .. } => {
+                diagnostic.with_label(format!("expected {}", expected.describe()))
+            }
+            ParseError::NoPrefixParseFn { .. } => diagnostic.with_label("expected an expression here"),
+            ParseError::InvalidIntegerLiteral { .. } => diagnostic
+                .with_label("does not fit in a 64-bit signed integer")
+                .with_note(format!("integers range from {} to {}", i64::MIN, i64::MAX)),
+            ParseError::UnterminatedBlock { .. } => diagnostic
+                .with_label("this `{` is never closed")
+                .with_help("add a `}` to close the block"),
+            ParseError::Lex(LexError::UnterminatedString { .. }) => diagnostic
+                .with_label("this string is never closed")
+                .with_help("add a `\"` to end the string"),
+            ParseError::Lex(LexError::InvalidEscape { .. }) => diagnostic
+                .with_label("unknown escape")
+                .with_note(r#"valid escapes are \n, \t, \r, \", \\ and \u{...} with 1 to 6 hex digits"#),
+            ParseError::Lex(LexError::UnterminatedComment { .. }) => diagnostic
+                .with_label("this comment is never closed")
+                .with_help("add a `*/` to end the comment; block comments nest, so each `/*` needs one"),
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(err: &CompileError) -> Self {
+        Diagnostic::error("E0200", err.message.clone(), err.span)
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(err: &RuntimeError) -> Self {
+        Diagnostic::error("E0100", err.message.clone(), err.span)
+    }
+}
+
+/// Renders `diagnostic` against `source` in the style of rustc:
+///
+/// ```text
+/// error[E0001]: expected `=`, found integer
+///  --> script.mk:1:7
+///   |
+/// 1 | let x 5;
+///   |       ^ expected `=`
+/// ```
+///
+/// `name` is the file the source came from. Spans running over several
+/// lines are underlined up to the end of their first line. Carets line up
+/// under wide characters such as CJK ideographs, which take two columns.
+pub fn render(diagnostic: &Diagnostic, name: &str, source: &str, color: bool) -> String {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+
+    let start = diagnostic.span.start;
+    let end = diagnostic.span.end;
+    let line = source.lines().nth(start.line - 1).unwrap_or("");
+    let line_number = start.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    // pad with the line's own tabs, and as many spaces as each other
+    // character is wide, so the carets stay aligned
+    let padding: String = line.chars()
+        .take(start.column - 1)
+        .map(|c| if c == '\t' { "\t".to_string() } else { " ".repeat(unicode::width(c)) })
+        .collect();
+    let underlined = line.chars().skip(start.column - 1);
+    let width = if end.line == start.line {
+        underlined.take(end.column.saturating_sub(start.column)).map(unicode::width).sum::<usize>()
+    } else {
+        underlined.map(unicode::width).sum()
+    }.max(1);
+
+    let mut output = String::new();
+    output.push_str(&paint(RED, &format!("error[{}]", diagnostic.code)));
+    output.push_str(&paint(BOLD, &format!(": {}", diagnostic.message)));
+    output.push('\n');
+    output.push_str(&format!("{gutter}{} {name}:{start}\n", paint(BLUE, "-->")));
+    output.push_str(&format!("{gutter} {}\n", paint(BLUE, "|")));
+    output.push_str(&format!("{} {line}\n", paint(BLUE, &format!("{line_number} |"))));
+
+    let mut underline = "^".repeat(width);
+    if let Some(label) = &diagnostic.label {
+        underline.push(' ');
+        underline.push_str(label);
+    }
+    output.push_str(&format!("{gutter} {} {padding}{}\n", paint(BLUE, "|"), paint(RED, &underline)));
+
+    if !diagnostic.notes.is_empty() || diagnostic.help.is_some() {
+        output.push_str(&format!("{gutter} {}\n", paint(BLUE, "|")));
+    }
+    for note in &diagnostic.notes {
+        output.push_str(&format!("{gutter} {} {}: {note}\n", paint(BLUE, "="), paint(BOLD, "note")));
+    }
+    if let Some(help) = &diagnostic.help {
+        output.push_str(&format!("{gutter} {} {}: {help}\n", paint(BLUE, "="), paint(BOLD, "help")));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse_diagnostics(source: &str) -> Vec<Diagnostic> {
+        let mut parser = Parser::new(Lexer::new(source));
+        let errors = parser.parse_program().expect_err("source should not parse");
+
+        errors.iter().map(Diagnostic::from).collect()
+    }
+
+    #[test]
+    fn test_render_unexpected_token() {
+        let source = "let a = 1;\nlet x 5;\n";
+        let diagnostic = &parse_diagnostics(source)[0];
+
+        assert_eq!(render(diagnostic, "script.mk", source, false), concat!(
+            "error[E0001]: expected `=`, found integer\n",
+            " --> script.mk:2:7\n",
+            "  |\n",
+            "2 | let x 5;\n",
+            "  |       ^ expected `=`\n",
+        ));
+    }
+
+    #[test]
+    fn test_render_notes_and_help() {
+        let source = "let big = 99999999999999999999;";
+        let diagnostic = &parse_diagnostics(source)[0];
+
+        assert_eq!(render(diagnostic, "<repl>", source, false), concat!(
+            "error[E0003]: integer literal `99999999999999999999` is out of range\n",
+            " --> <repl>:1:11\n",
+            "  |\n",
+            "1 | let big = 99999999999999999999;\n",
+            "  |           ^^^^^^^^^^^^^^^^^^^^ does not fit in a 64-bit signed integer\n",
+            "  |\n",
+            "  = note: integers range from -9223372036854775808 to 9223372036854775807\n",
+        ));
+
+        let source = "if (x) {\n\tx";
+        let diagnostic = &parse_diagnostics(source)[0];
+
+        assert_eq!(render(diagnostic, "<repl>", source, false), concat!(
+            "error[E0004]: unclosed block\n",
+            " --> <repl>:1:8\n",
+            "  |\n",
+            "1 | if (x) {\n",
+            "  |        ^ this `{` is never closed\n",
+            "  |\n",
+            "  = help: add a `}` to close the block\n",
+        ));
+    }
+
+    #[test]
+    fn test_render_keeps_tabs_and_wide_gutters() {
+        let source = format!("{}\t\tfoo + true;", "\n".repeat(9));
+        let diagnostic = Diagnostic::error("E0100", "type mismatch: INTEGER + BOOLEAN".to_string(), Span {
+            start: crate::token::Position { line: 10, column: 7, offset: 15 },
+            end: crate::token::Position { line: 10, column: 8, offset: 16 },
+        });
+
+        assert_eq!(render(&diagnostic, "script.mk", &source, false), concat!(
+            "error[E0100]: type mismatch: INTEGER + BOOLEAN\n",
+            "  --> script.mk:10:7\n",
+            "   |\n",
+            "10 | \t\tfoo + true;\n",
+            "   | \t\t    ^\n",
+        ));
+    }
+
+    #[test]
+    fn test_render_aligns_wide_characters() {
+        let source = "数量 + trüe + 😀;";
+        let diagnostics = parse_diagnostics(source);
+
+        assert_eq!(render(&diagnostics[0], "script.mk", source, false), concat!(
+            "error[E0002]: expected an expression, found illegal character\n",
+            " --> script.mk:1:13\n",
+            "  |\n",
+            "1 | 数量 + trüe + 😀;\n",
+            "  |               ^^ expected an expression here\n",
+        ));
+    }
+
+    #[test]
+    fn test_render_with_color() {
+        let source = "let = 1;";
+        let diagnostic = &parse_diagnostics(source)[0];
+        let rendered = render(diagnostic, "<repl>", source, true);
+
+        assert!(rendered.starts_with("\x1b[1;31merror[E0001]\x1b[0m\x1b[1m: expected identifier, found `=`\x1b[0m\n"));
+        assert!(rendered.contains("\x1b[1;31m^ expected identifier\x1b[0m"));
+    }
+}
@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use crate::ast::{BlockStatement, Identifier};
+use crate::builtins::Registry;
+use crate::object::{Function, Object};
+
+pub type Env = Rc<RefCell<Environment>>;
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Binding>,
+    outer: Option<Env>,
+    // shared by every scope of one program
+    builtins: Rc<RefCell<Registry>>,
+}
+
+#[derive(Debug)]
+enum Binding {
+    Value(Object),
+    // A function bound in the very scope it closes over. Holding that scope
+    // strongly would make the environment own itself, so only a weak pointer
+    // is kept here and a strong one is handed back out on lookup. Longer
+    // cycles, such as a closure stored into an outer scope of the one it
+    // captured, are not broken.
+    Recursive {
+        parameters: Rc<Vec<Identifier>>,
+        body: Rc<BlockStatement>,
+        env: Weak<RefCell<Environment>>,
+    },
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    /// A global scope whose builtins come from `builtins` instead of the
+    /// standard registry. Natives registered there later are picked up too.
+    pub fn with_builtins(builtins: Rc<RefCell<Registry>>) -> Env {
+        Rc::new(RefCell::new(Environment { builtins, ..Default::default() }))
+    }
+
+    pub fn new_enclosed(outer: &Env) -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            outer: Some(Rc::clone(outer)),
+            builtins: Rc::clone(&outer.borrow().builtins),
+        }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(Binding::Value(value)) => Some(value.clone()),
+            Some(Binding::Recursive { parameters, body, env }) => {
+                env.upgrade().map(|env| Object::Function(Rc::new(Function {
+                    parameters: Rc::clone(parameters),
+                    body: Rc::clone(body),
+                    env,
+                })))
+            }
+            None => self.outer.as_ref().and_then(|outer| outer.borrow().get(name)),
+        }
+    }
+
+    pub fn builtin(&self, name: &str) -> Option<Object> {
+        self.builtins.borrow().lookup(name)
+    }
+
+    pub fn set(&mut self, name: &str, value: Object) {
+        let binding = match value {
+            Object::Function(func) if std::ptr::eq(func.env.as_ptr(), self) => Binding::Recursive {
+                parameters: Rc::clone(&func.parameters),
+                body: Rc::clone(&func.body),
+                env: Rc::downgrade(&func.env),
+            },
+            value => Binding::Value(value),
+        };
+        self.store.insert(name.to_string(), binding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::eval;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn eval_in(input: &str, env: &Env) -> Object {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().expect("input should parse");
+
+        eval(&program, env)
+    }
+
+    #[test]
+    fn test_enclosed_lookup_and_shadowing() {
+        let outer = Environment::new();
+        outer.borrow_mut().set("a", Object::Integer(1));
+        outer.borrow_mut().set("b", Object::Integer(2));
+
+        let inner = Environment::new_enclosed(&outer);
+        inner.borrow_mut().set("a", Object::Integer(10));
+
+        assert!(matches!(inner.borrow().get("a"), Some(Object::Integer(10))));
+        assert!(matches!(inner.borrow().get("b"), Some(Object::Integer(2))));
+        assert!(matches!(outer.borrow().get("a"), Some(Object::Integer(1))));
+        assert!(inner.borrow().get("c").is_none());
+    }
+
+    #[test]
+    fn test_global_function_does_not_keep_environment_alive() {
+        let env = Environment::new();
+        eval_in("let f = fn(x) { f(x) }; let g = f;", &env);
+
+        let weak = Rc::downgrade(&env);
+        drop(env);
+
+        assert!(weak.upgrade().is_none(), "environment leaked through a self-referencing closure");
+    }
+
+    #[test]
+    fn test_stored_closure_keeps_its_scope_alive() {
+        let env = Environment::new();
+        eval_in("let g = fn() { g }; g();", &env);
+        assert_eq!((Rc::strong_count(&env), Rc::weak_count(&env)), (1, 1));
+
+        // a known leak: only the binding to its own name is held weakly
+        eval_in("let a = [g]; let h = {1: g}; a[0](); h[1]();", &env);
+        assert_eq!((Rc::strong_count(&env), Rc::weak_count(&env)), (3, 1));
+
+        eval_in("let a = 0; let h = 0;", &env);
+        assert_eq!((Rc::strong_count(&env), Rc::weak_count(&env)), (1, 1));
+    }
+
+    #[test]
+    fn test_returned_closure_keeps_its_scope_alive() {
+        let env = Environment::new();
+        let counter = eval_in(
+            "let make = fn() { let loop = fn(n) { if (n == 0) { 0 } else { loop(n - 1) } }; loop }; make()",
+            &env,
+        );
+
+        let scope = match &counter {
+            Object::Function(func) => Rc::downgrade(&func.env),
+            other => panic!("object is not Function. got {:?}", other),
+        };
+        assert!(scope.upgrade().is_some(), "closure lost the scope it was defined in");
+
+        let caller = Environment::new();
+        caller.borrow_mut().set("counter", counter);
+        assert!(matches!(eval_in("counter(3)", &caller), Object::Integer(0)));
+
+        drop(caller);
+        drop(env);
+        assert!(scope.upgrade().is_none(), "closure scope leaked after the environment was dropped");
+    }
+}
@@ -0,0 +1,880 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use crate::ast::{Program, StatementNode, ExpressionNode, BlockStatement, IfExpression, CallExpression, HashLiteral};
+use crate::environment::{Env, Environment};
+use crate::macros;
+use crate::object::{Object, Function, HashPair, RuntimeError};
+use crate::token::Span;
+
+// as many nested calls as the VM has frames for besides the main program's
+const MAX_CALL_DEPTH: usize = 1023;
+
+/// How much native stack evaluation may use unless told otherwise: half of
+/// the 2 MiB a spawned thread gets by default, leaving the rest to the host.
+pub const DEFAULT_MAX_STACK: usize = 1024 * 1024;
+
+thread_local! {
+    // how many function calls are being evaluated, to stop runaway recursion
+    // with an error before it overflows the native stack
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    // the lowest stack address a call may start at, or 0 outside
+    // `with_max_stack`
+    static STACK_LIMIT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Runs `f`, inside which a call fails with "stack overflow" once
+/// evaluation has used `max_stack` bytes of native stack below this point,
+/// however few calls deep it is. Nested uses keep the outermost limit.
+pub(crate) fn with_max_stack<T>(max_stack: usize, f: impl FnOnce() -> T) -> T {
+    if STACK_LIMIT.get() != 0 {
+        return f();
+    }
+
+    STACK_LIMIT.set(stack_address().saturating_sub(max_stack).max(1));
+    let result = f();
+    STACK_LIMIT.set(0);
+    result
+}
+
+// roughly how far the native stack has grown, which is downwards on every
+// platform Rust runs on
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+pub fn eval(program: &Program, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &program.statements {
+        result = eval_statement(statement, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error(_) => return result,
+            _ => {}
+        }
+    }
+
+    result
+}
+
+pub(crate) fn eval_block_statement(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &block.statements {
+        result = eval_statement(statement, env);
+
+        // leave the return value wrapped so enclosing blocks stop too
+        if let Object::ReturnValue(_) | Object::Error(_) = result {
+            return result;
+        }
+    }
+
+    result
+}
+
+fn eval_statement(statement: &StatementNode, env: &Env) -> Object {
+    match statement {
+        StatementNode::Let(stmt) => {
+            let value = match &stmt.value {
+                Some(value) => eval_expression(value, env),
+                None => Object::Null,
+            };
+            if value.is_error() {
+                return value;
+            }
+            env.borrow_mut().set(&stmt.name.value, value);
+            Object::Null
+        }
+        StatementNode::Return(stmt) => {
+            let value = match &stmt.return_value {
+                Some(value) => eval_expression(value, env),
+                None => Object::Null,
+            };
+            if value.is_error() {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        }
+        StatementNode::Expression(stmt) => match &stmt.expression {
+            Some(expression) => eval_expression(expression, env),
+            None => Object::Null,
+        },
+    }
+}
+
+pub(crate) fn eval_expression(expression: &ExpressionNode, env: &Env) -> Object {
+    match expression {
+        ExpressionNode::Integer(lit) => Object::Integer(lit.value),
+        ExpressionNode::Boolean(lit) => Object::Boolean(lit.value),
+        ExpressionNode::String(lit) => Object::String(lit.value.clone()),
+        ExpressionNode::IdentifierNode(ident) => {
+            let env = env.borrow();
+            match env.get(&ident.value).or_else(|| env.builtin(&ident.value)) {
+                Some(builtin) => builtin,
+                None => error(ident.token.span, format!("identifier not found: {}", ident.value)),
+            }
+        }
+        ExpressionNode::Prefix(exp) => {
+            let right = eval_expression(&exp.right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_prefix_expression(exp.token.span, &exp.operator, right)
+        }
+        ExpressionNode::Infix(exp) => {
+            let left = eval_expression(&exp.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let right = eval_expression(&exp.right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_infix_expression(exp.token.span, &exp.operator, left, right)
+        }
+        ExpressionNode::If(exp) => eval_if_expression(exp, env),
+        ExpressionNode::Function(func) => Object::Function(Rc::new(Function {
+            parameters: Rc::new(func.parameters.clone()),
+            body: Rc::new(func.body.clone()),
+            env: Rc::clone(env),
+        })),
+        ExpressionNode::Macro(mac) => error(mac.token.span, macros::MISPLACED_MACRO.to_string()),
+        ExpressionNode::Call(call) => eval_call_expression(call, env),
+        ExpressionNode::Array(array) => match eval_expressions(&array.elements, env) {
+            Ok(elements) => Object::Array(Rc::new(elements)),
+            Err(err) => err,
+        },
+        ExpressionNode::Hash(hash) => eval_hash_literal(hash, env),
+        ExpressionNode::Index(exp) => {
+            let left = eval_expression(&exp.left, env);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(&exp.index, env);
+            if index.is_error() {
+                return index;
+            }
+            eval_index_expression(exp.token.span, left, index)
+        }
+    }
+}
+
+fn eval_expressions(expressions: &[ExpressionNode], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut result = Vec::with_capacity(expressions.len());
+
+    for expression in expressions {
+        let value = eval_expression(expression, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        result.push(value);
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn eval_index_expression(span: Span, left: Object, index: Object) -> Object {
+    match (&left, &index) {
+        (Object::Array(elements), Object::Integer(i)) => usize::try_from(*i).ok()
+            .and_then(|i| elements.get(i))
+            .cloned()
+            .unwrap_or(Object::Null),
+        (Object::Hash(pairs), _) => match index.hash_key() {
+            Some(key) => pairs.get(&key).map(|pair| pair.value.clone()).unwrap_or(Object::Null),
+            None => error(span, format!("unusable as hash key: {}", index.type_name())),
+        },
+        _ => error(span, format!("index operator not supported: {}", left.type_name())),
+    }
+}
+
+fn eval_hash_literal(hash: &HashLiteral, env: &Env) -> Object {
+    let mut pairs = BTreeMap::new();
+
+    for (key_node, value_node) in &hash.pairs {
+        let key = eval_expression(key_node, env);
+        if key.is_error() {
+            return key;
+        }
+
+        let hash_key = match key.hash_key() {
+            Some(hash_key) => hash_key,
+            None => return error(hash.token.span, format!("unusable as hash key: {}", key.type_name())),
+        };
+
+        let value = eval_expression(value_node, env);
+        if value.is_error() {
+            return value;
+        }
+
+        pairs.insert(hash_key, HashPair { key, value });
+    }
+
+    Object::Hash(Rc::new(pairs))
+}
+
+pub(crate) fn eval_prefix_expression(span: Span, operator: &str, right: Object) -> Object {
+    match operator {
+        "!" => Object::Boolean(!is_truthy(&right)),
+        "-" => match right {
+            Object::Integer(value) => match value.checked_neg() {
+                Some(value) => Object::Integer(value),
+                None => error(span, format!("integer overflow: -{}", value)),
+            },
+            _ => error(span, format!("unknown operator: -{}", right.type_name())),
+        },
+        _ => error(span, format!("unknown operator: {}{}", operator, right.type_name())),
+    }
+}
+
+pub(crate) fn eval_infix_expression(span: Span, operator: &str, left: Object, right: Object) -> Object {
+    match (&left, &right) {
+        (Object::Integer(l), Object::Integer(r)) => eval_integer_infix_expression(span, operator, *l, *r),
+        (Object::String(l), Object::String(r)) => eval_string_infix_expression(span, operator, l, r),
+        (Object::Boolean(l), Object::Boolean(r)) => match operator {
+            "==" => Object::Boolean(l == r),
+            "!=" => Object::Boolean(l != r),
+            _ => error(span, format!("unknown operator: {} {} {}", left.type_name(), operator, right.type_name())),
+        },
+        _ if left.type_name() != right.type_name() => {
+            error(span, format!("type mismatch: {} {} {}", left.type_name(), operator, right.type_name()))
+        }
+        _ => error(span, format!("unknown operator: {} {} {}", left.type_name(), operator, right.type_name())),
+    }
+}
+
+fn eval_integer_infix_expression(span: Span, operator: &str, left: i64, right: i64) -> Object {
+    let checked = match operator {
+        "+" => left.checked_add(right),
+        "-" => left.checked_sub(right),
+        "*" => left.checked_mul(right),
+        "/" => {
+            if right == 0 {
+                return error(span, "division by zero".to_string());
+            }
+            left.checked_div(right)
+        }
+        "<" => return Object::Boolean(left < right),
+        ">" => return Object::Boolean(left > right),
+        "==" => return Object::Boolean(left == right),
+        "!=" => return Object::Boolean(left != right),
+        _ => return error(span, format!("unknown operator: INTEGER {} INTEGER", operator)),
+    };
+
+    match checked {
+        Some(value) => Object::Integer(value),
+        None => error(span, format!("integer overflow: {} {} {}", left, operator, right)),
+    }
+}
+
+fn eval_string_infix_expression(span: Span, operator: &str, left: &str, right: &str) -> Object {
+    match operator {
+        "+" => Object::String(format!("{}{}", left, right)),
+        "==" => Object::Boolean(left == right),
+        "!=" => Object::Boolean(left != right),
+        "<" => Object::Boolean(left < right),
+        ">" => Object::Boolean(left > right),
+        _ => error(span, format!("unknown operator: STRING {} STRING", operator)),
+    }
+}
+
+fn eval_if_expression(exp: &IfExpression, env: &Env) -> Object {
+    let condition = eval_expression(&exp.condition, env);
+    if condition.is_error() {
+        return condition;
+    }
+
+    if is_truthy(&condition) {
+        eval_block_statement(&exp.consequence, env)
+    } else if let Some(alternative) = &exp.alternative {
+        eval_block_statement(alternative, env)
+    } else {
+        Object::Null
+    }
+}
+
+fn eval_call_expression(call: &CallExpression, env: &Env) -> Object {
+    // the argument to `quote` is kept as code rather than evaluated
+    if macros::is_call_to(call, "quote") {
+        return macros::quote(call, env);
+    }
+
+    let function = eval_expression(&call.function, env);
+    if function.is_error() {
+        return function;
+    }
+
+    let args = match eval_expressions(&call.arguments, env) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+
+    apply_function(call.token.span, function, args)
+}
+
+fn apply_function(span: Span, function: Object, args: Vec<Object>) -> Object {
+    let func = match function {
+        Object::Function(func) => func,
+        Object::Builtin(builtin) => {
+            return (builtin.func)(&args).unwrap_or_else(|err| error(span, err.message));
+        }
+        other => return error(span, format!("not a function: {}", other.type_name())),
+    };
+
+    if func.parameters.len() != args.len() {
+        return error(span, format!(
+            "wrong number of arguments: want={}, got={}",
+            func.parameters.len(),
+            args.len()
+        ));
+    }
+
+    let depth = CALL_DEPTH.get();
+    if depth == MAX_CALL_DEPTH || stack_address() < STACK_LIMIT.get() {
+        return error(span, "stack overflow".to_string());
+    }
+
+    let call_env = Environment::new_enclosed(&func.env);
+    for (param, arg) in func.parameters.iter().zip(args) {
+        call_env.borrow_mut().set(&param.value, arg);
+    }
+
+    CALL_DEPTH.set(depth + 1);
+    let result = eval_block_statement(&func.body, &call_env);
+    CALL_DEPTH.set(depth);
+
+    match result {
+        Object::ReturnValue(value) => *value,
+        other => other,
+    }
+}
+
+fn error(span: Span, message: String) -> Object {
+    Object::Error(RuntimeError::new(span, message))
+}
+
+pub(crate) fn is_truthy(object: &Object) -> bool {
+    match object {
+        Object::Null => false,
+        Object::Boolean(value) => *value,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::ast::Node;
+    use std::cell::RefCell;
+    use crate::builtins::Registry;
+    use crate::object::HashKey;
+
+    #[test]
+    fn test_eval_integer_expression() {
+        let tests = [
+            ("5", 5),
+            ("10", 10),
+            ("-5", -5),
+            ("-10", -10),
+            ("5 + 5 + 5 + 5 - 10", 10),
+            ("2 * 2 * 2 * 2 * 2", 32),
+            ("-50 + 100 + -50", 0),
+            ("5 * 2 + 10", 20),
+            ("5 + 2 * 10", 25),
+            ("20 + 2 * -10", 0),
+            ("50 / 2 * 2 + 10", 60),
+            ("2 * (5 + 10)", 30),
+            ("3 * 3 * 3 + 10", 37),
+            ("3 * (3 * 3) + 10", 37),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_eval_boolean_expression() {
+        let tests = [
+            ("true", true),
+            ("false", false),
+            ("1 < 2", true),
+            ("1 > 2", false),
+            ("1 < 1", false),
+            ("1 > 1", false),
+            ("1 == 1", true),
+            ("1 != 1", false),
+            ("1 == 2", false),
+            ("1 != 2", true),
+            ("true == true", true),
+            ("false == false", true),
+            ("true == false", false),
+            ("true != false", true),
+            ("(1 < 2) == true", true),
+            ("(1 < 2) == false", false),
+            ("(1 > 2) == true", false),
+            ("(1 > 2) == false", true),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_bang_operator() {
+        let tests = [
+            ("!true", false),
+            ("!false", true),
+            ("!5", false),
+            ("!!true", true),
+            ("!!false", false),
+            ("!!5", true),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_if_else_expressions() {
+        let tests = [
+            ("if (true) { 10 }", Some(10)),
+            ("if (false) { 10 }", None),
+            ("if (1) { 10 }", Some(10)),
+            ("if (1 < 2) { 10 }", Some(10)),
+            ("if (1 > 2) { 10 }", None),
+            ("if (1 > 2) { 10 } else { 20 }", Some(20)),
+            ("if (1 < 2) { 10 } else { 20 }", Some(10)),
+        ];
+
+        for (input, expected) in tests {
+            match expected {
+                Some(value) => test_integer_object(test_eval(input), value),
+                None => test_null_object(test_eval(input)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let tests = [
+            ("return 10;", 10),
+            ("return 10; 9;", 10),
+            ("return 2 * 5; 9;", 10),
+            ("9; return 2 * 5; 9;", 10),
+            ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", 10),
+            ("let f = fn(x) { if (x > 1) { if (x > 2) { return x; } } return 0; }; f(5) + 1", 6),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_error_handling() {
+        let tests = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN"),
+            ("5 + true; 5;", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false;", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("5; true + false; 5", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { true + false; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("if (10 > 1) { if (10 > 1) { return true + false; } return 1; }", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("foobar", "identifier not found: foobar"),
+            ("5 / 0", "division by zero"),
+            (r#""Hello" - "World""#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+            ("9223372036854775807 + 1", "integer overflow: 9223372036854775807 + 1"),
+            ("5(1)", "not a function: INTEGER"),
+            ("fn(x) { x }(1, 2)", "wrong number of arguments: want=1, got=2"),
+            ("1[0]", "index operator not supported: INTEGER"),
+            (r#"{"name": "Monkey"}[fn(x) { x }];"#, "unusable as hash key: FUNCTION"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY"),
+            ("{len: 1}", "unusable as hash key: BUILTIN"),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(err) => assert_eq!(err.message, expected, "wrong error message for {}", input),
+                other => panic!("no error object returned for {}. got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let tests = [
+            ("5 + true;", "1:3"),
+            ("let x = 1;\nlet y = x;\n  -true", "3:3"),
+            ("let f = fn(a) { a / 0 };\nf(1)", "1:19"),
+            ("foobar", "1:1"),
+            ("let f = 1;\nf(1)", "2:2"),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(err) => assert_eq!(err.span.start.to_string(), expected, "wrong error position for {}", input),
+                other => panic!("no error object returned for {}. got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_let_statements() {
+        let tests = [
+            ("let a = 5; a;", 5),
+            ("let a = 5 * 5; a;", 25),
+            ("let a = 5; let b = a; b;", 5),
+            ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_function_object() {
+        match test_eval("fn(x) { x + 2; };") {
+            Object::Function(func) => {
+                assert_eq!(func.parameters.len(), 1, "function has wrong parameters. got {:?}", func.parameters);
+                assert_eq!(func.parameters[0].value, "x");
+                assert_eq!(func.body.print(), "{ (x + 2); }");
+            }
+            other => panic!("object is not Function. got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_function_application() {
+        let tests = [
+            ("let identity = fn(x) { x; }; identity(5);", 5),
+            ("let identity = fn(x) { return x; }; identity(5);", 5),
+            ("let double = fn(x) { x * 2; }; double(5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
+            ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
+            ("fn(x) { x; }(5)", 5),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_string_literal() {
+        match test_eval(r#""Hello World!""#) {
+            Object::String(value) => assert_eq!(value, "Hello World!"),
+            other => panic!("object is not String. got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        match test_eval(r#"let greet = fn(name) { "Hello" + ", " + name + "!\n" }; greet("Wörld")"#) {
+            Object::String(value) => assert_eq!(value, "Hello, Wörld!\n"),
+            other => panic!("object is not String. got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let tests = [
+            (r#""a" == "a""#, true),
+            (r#""a" == "b""#, false),
+            (r#""a" != "b""#, true),
+            (r#""a" + "b" == "ab""#, true),
+            (r#""apple" < "banana""#, true),
+            (r#""apple" > "banana""#, false),
+        ];
+
+        for (input, expected) in tests {
+            test_boolean_object(test_eval(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_builtin_functions() {
+        let tests = [
+            (r#"len("")"#, Object::Integer(0)),
+            (r#"len("four")"#, Object::Integer(4)),
+            (r#"len("größe")"#, Object::Integer(5)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            ("len([])", Object::Integer(0)),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            ("rest([1, 2, 3])", Object::String("[2, 3]".to_string())),
+            ("rest([])", Object::Null),
+            ("push([], 1)", Object::String("[1]".to_string())),
+            ("let a = [1]; push(a, 2); a", Object::String("[1]".to_string())),
+            (r#"len({"a": 1, "b": 2})"#, Object::Integer(2)),
+            (r#"puts("hello", 1)"#, Object::Null),
+            ("type(1)", Object::String("INTEGER".to_string())),
+            ("type(len)", Object::String("BUILTIN".to_string())),
+            (r#"str([1, "a", true])"#, Object::String("[1, a, true]".to_string())),
+            ("str(12) + str(3)", Object::String("123".to_string())),
+            (r#"int(" -42 ")"#, Object::Integer(-42)),
+            ("int(true)", Object::Integer(1)),
+            ("int(7)", Object::Integer(7)),
+            ("range(3)", Object::String("[0, 1, 2]".to_string())),
+            ("range(2, 5)", Object::String("[2, 3, 4]".to_string())),
+            ("range(3, 1)", Object::String("[]".to_string())),
+            (r#"let len = fn(x) { 0 }; len("abc")"#, Object::Integer(0)),
+        ];
+
+        for (input, expected) in tests {
+            let evaluated = test_eval(input);
+            match expected {
+                Object::Integer(value) => test_integer_object(evaluated, value),
+                Object::Null => test_null_object(evaluated),
+                // arrays are compared through their inspect output
+                Object::String(inspected) => assert_eq!(evaluated.inspect(), inspected, "wrong result for {}", input),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtin_errors() {
+        let tests = [
+            ("len(1)", "argument to `len` not supported, got INTEGER"),
+            (r#"len("one", "two")"#, "wrong number of arguments. got=2, want=1"),
+            ("first(1)", "argument to `first` must be ARRAY, got INTEGER"),
+            ("push(1, 1)", "argument to `push` must be ARRAY, got INTEGER"),
+            ("push([])", "wrong number of arguments. got=1, want=2"),
+            ("type()", "wrong number of arguments. got=0, want=1"),
+            (r#"int("4x")"#, r#"could not parse "4x" as an integer"#),
+            ("int([])", "argument to `int` not supported, got ARRAY"),
+            ("range(1, 2, 3)", "wrong number of arguments. got=3, want=1 or 2"),
+            (r#"range("a")"#, "argument to `range` must be INTEGER, got STRING"),
+            ("range(-9223372036854775807, 9223372036854775807)", "range too long (the limit is 16777216 elements)"),
+            ("range(16777217)", "range too long (the limit is 16777216 elements)"),
+        ];
+
+        for (input, expected) in tests {
+            match test_eval(input) {
+                Object::Error(err) => {
+                    assert_eq!(err.message, expected, "wrong error message for {}", input);
+                    assert_eq!(err.span.start.column, input.find('(').unwrap() + 1, "error should point at the call");
+                }
+                other => panic!("no error object returned for {}. got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_registered_builtins() {
+        fn double(args: &[Object]) -> Result<Object, RuntimeError> {
+            match args {
+                [Object::Integer(value)] => Ok(Object::Integer(value * 2)),
+                _ => Err(RuntimeError::new(Default::default(), "double takes one integer".to_string())),
+            }
+        }
+
+        let mut registry = Registry::standard();
+        registry.register("double", double);
+        let env = Environment::with_builtins(Rc::new(RefCell::new(registry)));
+
+        let program = Parser::new(Lexer::new("let f = fn(x) { double(x) + len([]) }; f(21)")).parse_program().unwrap();
+        test_integer_object(eval(&program, &env), 42);
+
+        let program = Parser::new(Lexer::new("1;\n  double(true)")).parse_program().unwrap();
+        match eval(&program, &env) {
+            Object::Error(err) => {
+                assert_eq!(err.message, "double takes one integer");
+                assert_eq!(err.span.start.to_string(), "2:9", "error should point at the call");
+            }
+            other => panic!("no error object returned. got {:?}", other),
+        }
+
+        let program = Parser::new(Lexer::new("double(1)")).parse_program().unwrap();
+        match eval(&program, &Environment::new()) {
+            Object::Error(err) => assert_eq!(err.message, "identifier not found: double"),
+            other => panic!("no error object returned. got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_array_literals() {
+        match test_eval("[1, 2 * 2, 3 + 3]") {
+            Object::Array(elements) => {
+                assert_eq!(elements.len(), 3, "array has wrong num of elements. got {}", elements.len());
+                test_integer_object(elements[0].clone(), 1);
+                test_integer_object(elements[1].clone(), 4);
+                test_integer_object(elements[2].clone(), 6);
+            }
+            other => panic!("object is not Array. got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_array_index_expressions() {
+        let tests = [
+            ("[1, 2, 3][0]", Some(1)),
+            ("[1, 2, 3][1]", Some(2)),
+            ("[1, 2, 3][2]", Some(3)),
+            ("let i = 0; [1][i];", Some(1)),
+            ("[1, 2, 3][1 + 1];", Some(3)),
+            ("let myArray = [1, 2, 3]; myArray[2];", Some(3)),
+            ("let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];", Some(6)),
+            ("let myArray = [1, 2, 3]; let i = myArray[0]; myArray[i]", Some(2)),
+            ("[1, 2, 3][3]", None),
+            ("[1, 2, 3][-1]", None),
+        ];
+
+        for (input, expected) in tests {
+            match expected {
+                Some(value) => test_integer_object(test_eval(input), value),
+                None => test_null_object(test_eval(input)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_array_functions_in_monkey() {
+        let input = r#"
+        let map = fn(arr, f) {
+            let iter = fn(arr, accumulated) {
+                if (len(arr) == 0) {
+                    accumulated
+                } else {
+                    iter(rest(arr), push(accumulated, f(first(arr))));
+                }
+            };
+            iter(arr, []);
+        };
+        let double = fn(x) { x * 2 };
+        map([1, 2, 3, 4], double);
+        "#;
+
+        assert_eq!(test_eval(input).inspect(), "[2, 4, 6, 8]");
+    }
+
+    #[test]
+    fn test_hash_literals() {
+        let input = r#"
+        let two = "two";
+        {
+            "one": 10 - 9,
+            two: 1 + 1,
+            "thr" + "ee": 6 / 2,
+            4: 4,
+            true: 5,
+            false: 6
+        }
+        "#;
+
+        let pairs = match test_eval(input) {
+            Object::Hash(pairs) => pairs,
+            other => panic!("eval didn't return Hash. got {:?}", other),
+        };
+
+        let expected = [
+            (HashKey::String("one".to_string()), 1),
+            (HashKey::String("two".to_string()), 2),
+            (HashKey::String("three".to_string()), 3),
+            (HashKey::Integer(4), 4),
+            (HashKey::Boolean(true), 5),
+            (HashKey::Boolean(false), 6),
+        ];
+        assert_eq!(pairs.len(), expected.len(), "hash has wrong num of pairs. got {}", pairs.len());
+
+        for (key, value) in expected {
+            match pairs.get(&key) {
+                Some(pair) => test_integer_object(pair.value.clone(), value),
+                None => panic!("no pair for given key {:?} in pairs", key),
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_index_expressions() {
+        let tests = [
+            (r#"{"foo": 5}["foo"]"#, Some(5)),
+            (r#"{"foo": 5}["bar"]"#, None),
+            (r#"let key = "foo"; {"foo": 5}[key]"#, Some(5)),
+            (r#"{}["foo"]"#, None),
+            ("{5: 5}[5]", Some(5)),
+            ("{true: 5}[true]", Some(5)),
+            ("{false: 5}[false]", Some(5)),
+            ("{1: 1, 1: 2}[1]", Some(2)),
+        ];
+
+        for (input, expected) in tests {
+            match expected {
+                Some(value) => test_integer_object(test_eval(input), value),
+                None => test_null_object(test_eval(input)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_inspect() {
+        let evaluated = test_eval(r#"{"b": [1, 2], 2: true, "a": {}, 1: "one"}"#);
+
+        assert_eq!(evaluated.inspect(), "{1: one, 2: true, a: {}, b: [1, 2]}");
+    }
+
+    #[test]
+    fn test_closures() {
+        let input = r#"
+        let newAdder = fn(x) { fn(y) { x + y } };
+        let addTwo = newAdder(2);
+        addTwo(3);
+        "#;
+
+        test_integer_object(test_eval(input), 5);
+    }
+
+    #[test]
+    fn test_lexical_scoping() {
+        let tests = [
+            ("let x = 10; let f = fn() { x }; f()", 10),
+            ("let x = 10; let f = fn(x) { x }; f(1) + x", 11),
+            ("let x = 10; let f = fn() { let x = 1; x }; f() + x", 11),
+            ("let first = 10; let second = 10; let third = 10; let ourFunction = fn(first) { let second = 20; first + second + third; }; ourFunction(20) + first + second;", 70),
+            ("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)", 610),
+        ];
+
+        for (input, expected) in tests {
+            test_integer_object(test_eval(input), expected);
+        }
+    }
+
+    fn test_eval(input: &str) -> Object {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let program = parser.parse_program().expect("input should parse");
+        let env = Environment::new();
+
+        eval(&program, &env)
+    }
+
+    fn test_integer_object(object: Object, expected: i64) {
+        match object {
+            Object::Integer(value) => assert_eq!(value, expected, "object has wrong value. got {}, want {}", value, expected),
+            other => panic!("object is not Integer. got {:?}", other),
+        }
+    }
+
+    fn test_boolean_object(object: Object, expected: bool) {
+        match object {
+            Object::Boolean(value) => assert_eq!(value, expected, "object has wrong value. got {}, want {}", value, expected),
+            other => panic!("object is not Boolean. got {:?}", other),
+        }
+    }
+
+    fn test_null_object(object: Object) {
+        assert!(matches!(object, Object::Null), "object is not Null. got {:?}", object);
+    }
+}
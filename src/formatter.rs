@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use crate::ast::{BlockStatement, ExpressionNode, Identifier, Node, Program, StatementNode};
+use crate::lexer::Lexer;
+use crate::parser::{precedence_map, Precedence};
+use crate::token::TokenKind;
+use crate::unicode;
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 80;
+
+/// Prints `program` back as canonical Monkey source: one statement per
+/// line, blocks indented by four spaces unless they are short enough to
+/// keep on one line, single spaces around binary operators and only the
+/// parentheses precedence needs. Blank lines between statements in
+/// `source`, the text `program` was parsed from, are kept, though runs of
+/// them shrink to one.
+///
+/// Comments in `source` are kept too. One after code on the same line stays
+/// there; any other goes on a line of its own before the statement or
+/// closing brace that follows it. A block comment inside an expression stays
+/// before the operand, operator or closing bracket it preceded, and a line
+/// comment among the elements of a call, array or hash puts them one per
+/// line.
+///
+/// Formatting the output again gives the same output.
+pub fn format(program: &Program, source: &str) -> String {
+    let (comments, closing) = scan(source);
+    let mut formatter = Formatter {
+        source,
+        comments,
+        next_comment: 0,
+        closing,
+        output: String::new(),
+        indent: 0,
+    };
+
+    let mut first = true;
+    formatter.statements(&program.statements, false, usize::MAX, &mut first);
+    formatter.comments_before(usize::MAX, &mut first);
+    formatter.output
+}
+
+struct Comment {
+    text: String,
+    offset: usize,
+    // whether code comes before it on its line
+    trailing: bool,
+    // the innermost bracket it is inside
+    enclosing: Option<usize>,
+}
+
+/// Finds the comments in `source`, and where each bracket is closed, by
+/// offset.
+fn scan(source: &str) -> (Vec<Comment>, HashMap<usize, usize>) {
+    let mut lexer = Lexer::new(source).with_comments();
+    let mut comments = vec![];
+    let mut open_brackets = vec![];
+    let mut closing = HashMap::new();
+    let mut last_line = None;
+
+    loop {
+        let token = lexer.next_token();
+        let offset = token.span.start.offset;
+        match token.kind {
+            TokenKind::EOF => break,
+            TokenKind::Comment => {
+                let text = if token.literal.starts_with("//") { token.literal.trim_end() } else { &token.literal };
+                let trailing = last_line == Some(token.span.start.line);
+                let enclosing = open_brackets.last().copied();
+                comments.push(Comment { text: text.to_string(), offset, trailing, enclosing });
+                continue;
+            }
+            TokenKind::LBrace | TokenKind::LParen | TokenKind::LBracket => open_brackets.push(offset),
+            TokenKind::RBrace | TokenKind::RParen | TokenKind::RBracket => {
+                if let Some(open) = open_brackets.pop() {
+                    closing.insert(open, offset);
+                }
+            }
+            _ => {}
+        }
+        last_line = Some(token.span.end.line);
+    }
+
+    (comments, closing)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Comment>,
+    // the first comment not written yet
+    next_comment: usize,
+    // where the bracket opened at each offset is closed
+    closing: HashMap<usize, usize>,
+    output: String,
+    indent: usize,
+}
+
+impl Formatter<'_> {
+    /// Writes each statement on its own line, along with the comments before
+    /// `end`. In a block, a final expression is the block's value and goes
+    /// without a semicolon. `first` is whether nothing has been written in
+    /// the block yet.
+    fn statements(&mut self, statements: &[StatementNode], in_block: bool, end: usize, first: &mut bool) {
+        // where the last statement, an `if` written without a `;`, ended
+        let mut open_if: Option<usize> = None;
+
+        for (i, statement) in statements.iter().enumerate() {
+            self.comments_before(statement_offset(statement), first);
+            self.start_line(statement_offset(statement), first);
+
+            let is_value = in_block && i == statements.len() - 1;
+            let start = self.output.len();
+            self.statement(statement, is_value);
+
+            // without a `;` the `if` would carry on into this statement, as
+            // a subtraction, call or index
+            if let Some(end) = open_if.take() {
+                if self.output[start..].starts_with(['-', '!', '(', '[']) {
+                    self.output.insert(end, ';');
+                }
+            }
+            if is_if_statement(statement) {
+                open_if = Some(self.output.len());
+            }
+
+            let next = statements.get(i + 1).map_or(end, statement_offset);
+            self.trailing_comments(next);
+            self.output.push('\n');
+        }
+    }
+
+    /// Writes the comments before `offset` on lines of their own.
+    fn comments_before(&mut self, offset: usize, first: &mut bool) {
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|c| c.offset < offset) {
+            let (text, offset) = (comment.text.clone(), comment.offset);
+            self.start_line(offset, first);
+            self.output.push_str(&text);
+            self.output.push('\n');
+            self.next_comment += 1;
+        }
+    }
+
+    /// Writes the comments that followed the code just written on its line,
+    /// up to `offset`. Nothing can follow a line comment.
+    fn trailing_comments(&mut self, offset: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|c| c.trailing && c.offset < offset) {
+            self.output.push(' ');
+            self.output.push_str(&comment.text);
+            self.next_comment += 1;
+            if comment.text.starts_with("//") {
+                break;
+            }
+        }
+    }
+
+    /// Writes the block comments before `offset` where the output has got to,
+    /// stopping at a line comment, which needs the rest of its line. They go
+    /// before what comes next if `leading`, and after what came before if
+    /// not.
+    fn inline_comments(&mut self, offset: usize, leading: bool) {
+        while let Some(comment) = self.comments.get(self.next_comment)
+            .filter(|c| c.offset < offset && !c.text.starts_with("//"))
+        {
+            if leading {
+                self.output.push_str(&comment.text);
+                self.output.push(' ');
+            } else {
+                self.output.push(' ');
+                self.output.push_str(&comment.text);
+            }
+            self.next_comment += 1;
+        }
+    }
+
+    /// Indents a new line for what starts at `offset` in the source, after a
+    /// blank line if there was one before it.
+    fn start_line(&mut self, offset: usize, first: &mut bool) {
+        let before = &self.source[..offset.min(self.source.len())];
+        if !*first && before[before.trim_end().len()..].matches('\n').count() > 1 {
+            self.output.push('\n');
+        }
+
+        self.output.push_str(&INDENT.repeat(self.indent));
+        *first = false;
+    }
+
+    fn statement(&mut self, statement: &StatementNode, is_value: bool) {
+        match statement {
+            StatementNode::Let(stmt) => {
+                self.output.push_str("let ");
+                self.output.push_str(&stmt.name.value);
+                if let Some(value) = &stmt.value {
+                    self.output.push_str(" = ");
+                    self.expression(value, Precedence::Lowest);
+                }
+                self.output.push(';');
+            }
+            StatementNode::Return(stmt) => {
+                self.output.push_str("return");
+                if let Some(value) = &stmt.return_value {
+                    self.output.push(' ');
+                    self.expression(value, Precedence::Lowest);
+                }
+                self.output.push(';');
+            }
+            StatementNode::Expression(stmt) => {
+                if let Some(expression) = &stmt.expression {
+                    self.expression(expression, Precedence::Lowest);
+                    // an `if` reads as a statement, and ends in a brace anyway
+                    if !is_value && !matches!(expression, ExpressionNode::If(_)) {
+                        self.output.push(';');
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes `block` on one line if it is a single expression or return
+    /// that fits, and indented over several lines otherwise.
+    fn block(&mut self, block: &BlockStatement) {
+        if !self.inline_block(block) {
+            self.multiline_block(block);
+        }
+    }
+
+    /// Tries to write `block` on one line, leaving the output untouched and
+    /// returning false if it doesn't fit or holds a comment.
+    fn inline_block(&mut self, block: &BlockStatement) -> bool {
+        let (open, close) = self.braces(block);
+        if self.comments[self.next_comment..].iter().any(|c| c.offset > open && c.offset < close) {
+            return false;
+        }
+
+        let statement = match block.statements.as_slice() {
+            [] => {
+                self.output.push_str("{}");
+                return true;
+            }
+            [statement @ (StatementNode::Expression(_) | StatementNode::Return(_))] => statement,
+            _ => return false,
+        };
+
+        let (start, next_comment) = (self.output.len(), self.next_comment);
+        self.output.push_str("{ ");
+        self.statement(statement, true);
+        self.output.push_str(" }");
+
+        let line = &self.output[self.output.rfind('\n').map_or(0, |i| i + 1)..];
+        if self.output[start..].contains('\n') || unicode::str_width(line) > MAX_WIDTH {
+            self.output.truncate(start);
+            self.next_comment = next_comment;
+            return false;
+        }
+        true
+    }
+
+    fn multiline_block(&mut self, block: &BlockStatement) {
+        let (_, close) = self.braces(block);
+        let mut first = true;
+
+        self.output.push_str("{\n");
+        self.indent += 1;
+        self.statements(&block.statements, true, close, &mut first);
+        self.comments_before(close, &mut first);
+        self.indent -= 1;
+
+        if first {
+            // nothing was written after all
+            self.output.pop();
+        } else {
+            self.output.push_str(&INDENT.repeat(self.indent));
+        }
+        self.output.push('}');
+    }
+
+    /// Where `block` opens and closes in the source.
+    fn braces(&self, block: &BlockStatement) -> (usize, usize) {
+        let open = block.token.span.start.offset;
+        (open, self.closing(open))
+    }
+
+    fn closing(&self, open: usize) -> usize {
+        self.closing.get(&open).copied().unwrap_or(open)
+    }
+
+    /// Writes `expression`, in parentheses if it binds more loosely than
+    /// `min` allows where it appears.
+    fn expression(&mut self, expression: &ExpressionNode, min: Precedence) {
+        self.inline_comments(expression_offset(expression), true);
+
+        let parenthesize = precedence(expression) < min;
+        if parenthesize {
+            self.output.push('(');
+        }
+
+        match expression {
+            ExpressionNode::IdentifierNode(ident) => self.output.push_str(&ident.value),
+            ExpressionNode::Integer(lit) => self.output.push_str(&lit.value.to_string()),
+            ExpressionNode::String(lit) => self.output.push_str(&lit.print()),
+            ExpressionNode::Boolean(lit) => self.output.push_str(&lit.value.to_string()),
+            ExpressionNode::Prefix(exp) => {
+                self.output.push_str(&exp.operator);
+                self.expression(&exp.right, Precedence::Prefix);
+            }
+            ExpressionNode::Infix(exp) => {
+                // operators are left-associative, so an equal one on the
+                // right needs parentheses but one on the left doesn't
+                let precedence = precedence_map(&exp.token.kind);
+                self.expression(&exp.left, precedence);
+                self.inline_comments(exp.token.span.start.offset, false);
+                self.output.push_str(&format!(" {} ", exp.operator));
+                self.expression(&exp.right, tighter(precedence));
+            }
+            ExpressionNode::If(exp) => {
+                self.output.push_str("if (");
+                self.expression(&exp.condition, Precedence::Lowest);
+                self.output.push_str(") ");
+
+                // both branches go on one line, or neither does
+                let start = self.output.len();
+                let inline = self.inline_block(&exp.consequence) && exp.alternative.as_ref().is_none_or(|alternative| {
+                    self.output.push_str(" else ");
+                    self.inline_block(alternative)
+                });
+                if !inline {
+                    self.output.truncate(start);
+                    self.multiline_block(&exp.consequence);
+                    if let Some(alternative) = &exp.alternative {
+                        self.output.push_str(" else ");
+                        self.multiline_block(alternative);
+                    }
+                }
+            }
+            ExpressionNode::Function(func) => {
+                self.output.push_str("fn");
+                self.parameters(&func.parameters);
+                self.block(&func.body);
+            }
+            ExpressionNode::Macro(mac) => {
+                self.output.push_str("macro");
+                self.parameters(&mac.parameters);
+                self.block(&mac.body);
+            }
+            ExpressionNode::Call(call) => {
+                self.expression(&call.function, Precedence::Call);
+                self.output.push('(');
+                self.list(call.token.span.start.offset, &call.arguments, expression_offset, |f, argument| {
+                    f.expression(argument, Precedence::Lowest);
+                });
+                self.output.push(')');
+            }
+            ExpressionNode::Array(array) => {
+                self.output.push('[');
+                self.list(array.token.span.start.offset, &array.elements, expression_offset, |f, element| {
+                    f.expression(element, Precedence::Lowest);
+                });
+                self.output.push(']');
+            }
+            ExpressionNode::Index(exp) => {
+                self.expression(&exp.left, Precedence::Call);
+                self.output.push('[');
+                self.expression(&exp.index, Precedence::Lowest);
+                self.inline_comments(self.closing(exp.token.span.start.offset), false);
+                self.output.push(']');
+            }
+            ExpressionNode::Hash(hash) => {
+                self.output.push('{');
+                self.list(hash.token.span.start.offset, &hash.pairs, |(key, _)| expression_offset(key), |f, (key, value)| {
+                    f.expression(key, Precedence::Lowest);
+                    f.output.push_str(": ");
+                    f.expression(value, Precedence::Lowest);
+                });
+                self.output.push('}');
+            }
+        }
+
+        if parenthesize {
+            self.output.push(')');
+        }
+    }
+
+    fn parameters(&mut self, parameters: &[Identifier]) {
+        let names: Vec<&str> = parameters.iter().map(|p| p.value.as_str()).collect();
+        self.output.push_str(&format!("({}) ", names.join(", ")));
+    }
+
+    /// Writes the comma-separated `items` of the brackets opened at `open`,
+    /// on one line unless a line comment among them needs one item per line.
+    /// `offset` is where an item starts in the source.
+    fn list<T>(&mut self, open: usize, items: &[T], offset: impl Fn(&T) -> usize, write: impl Fn(&mut Self, &T)) {
+        let close = self.closing(open);
+        let multiline = self.comments[self.next_comment..].iter()
+            .any(|c| c.enclosing == Some(open) && c.text.starts_with("//"));
+
+        if !multiline {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    self.output.push_str(", ");
+                }
+                write(self, item);
+            }
+            self.inline_comments(close, false);
+            return;
+        }
+
+        // a comment on the same line as the next item stays in front of it
+        let line_start = |offset: usize| self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let starts: Vec<usize> = items.iter().map(|item| line_start(offset(item))).collect();
+
+        let mut first = true;
+        self.output.push('\n');
+        self.indent += 1;
+        for (i, item) in items.iter().enumerate() {
+            self.comments_before(starts[i], &mut first);
+            self.start_line(offset(item), &mut first);
+            write(self, item);
+            if i + 1 < items.len() {
+                self.output.push(',');
+            }
+
+            self.trailing_comments(starts.get(i + 1).copied().unwrap_or(close));
+            self.output.push('\n');
+        }
+        self.comments_before(close, &mut first);
+        self.indent -= 1;
+        self.output.push_str(&INDENT.repeat(self.indent));
+    }
+}
+
+/// How tightly `expression` binds. Calls and indexes bind tighter than any
+/// operator; literals and anything else in brackets can't be split at all.
+fn precedence(expression: &ExpressionNode) -> Precedence {
+    match expression {
+        ExpressionNode::Infix(exp) => precedence_map(&exp.token.kind),
+        ExpressionNode::Prefix(_) => Precedence::Prefix,
+        ExpressionNode::Call(_) | ExpressionNode::Index(_) => Precedence::Call,
+        _ => Precedence::Index,
+    }
+}
+
+fn is_if_statement(statement: &StatementNode) -> bool {
+    matches!(statement, StatementNode::Expression(stmt) if matches!(stmt.expression, Some(ExpressionNode::If(_))))
+}
+
+fn statement_offset(statement: &StatementNode) -> usize {
+    match statement {
+        StatementNode::Let(stmt) => stmt.token.span.start.offset,
+        StatementNode::Return(stmt) => stmt.token.span.start.offset,
+        StatementNode::Expression(stmt) => stmt.token.span.start.offset,
+    }
+}
+
+/// Where `expression` starts in the source, leaving out any parentheses
+/// around it.
+fn expression_offset(expression: &ExpressionNode) -> usize {
+    match expression {
+        ExpressionNode::Infix(exp) => expression_offset(&exp.left),
+        ExpressionNode::Call(call) => expression_offset(&call.function),
+        ExpressionNode::Index(exp) => expression_offset(&exp.left),
+        ExpressionNode::IdentifierNode(ident) => ident.token.span.start.offset,
+        ExpressionNode::Integer(lit) => lit.token.span.start.offset,
+        ExpressionNode::String(lit) => lit.token.span.start.offset,
+        ExpressionNode::Boolean(lit) => lit.token.span.start.offset,
+        ExpressionNode::Prefix(exp) => exp.token.span.start.offset,
+        ExpressionNode::If(exp) => exp.token.span.start.offset,
+        ExpressionNode::Function(func) => func.token.span.start.offset,
+        ExpressionNode::Macro(mac) => mac.token.span.start.offset,
+        ExpressionNode::Array(array) => array.token.span.start.offset,
+        ExpressionNode::Hash(hash) => hash.token.span.start.offset,
+    }
+}
+
+fn tighter(precedence: Precedence) -> Precedence {
+    match precedence {
+        Precedence::Lowest => Precedence::Equals,
+        Precedence::Equals => Precedence::LessGreater,
+        Precedence::LessGreater => Precedence::Sum,
+        Precedence::Sum => Precedence::Product,
+        Precedence::Product => Precedence::Prefix,
+        Precedence::Prefix => Precedence::Call,
+        Precedence::Call | Precedence::Index => Precedence::Index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        Parser::new(Lexer::new(input)).parse_program().expect("input should parse")
+    }
+
+    fn format_source(input: &str) -> String {
+        format(&parse(input), input)
+    }
+
+    #[test]
+    fn test_format_statements() {
+        let tests = [
+            ("", ""),
+            ("let x=5", "let x = 5;\n"),
+            ("return   x", "return x;\n"),
+            ("puts(1)  puts(2)", "puts(1);\nputs(2);\n"),
+            ("let x = 1;\n\n\n\nlet y = 2;\nx + y", "let x = 1;\n\nlet y = 2;\nx + y;\n"),
+            ("  \n\nlet x = 1;", "let x = 1;\n"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(format_source(input), expected, "wrong formatting for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_expressions() {
+        let tests = [
+            ("-a * b", "-a * b;\n"),
+            ("-(a * b)", "-(a * b);\n"),
+            ("!-a", "!-a;\n"),
+            ("a + b + c", "a + b + c;\n"),
+            ("a + (b + c)", "a + (b + c);\n"),
+            ("(a + b) * c", "(a + b) * c;\n"),
+            ("a * (b * c) / d", "a * (b * c) / d;\n"),
+            ("((1 < 2)) == (3 > 4)", "1 < 2 == 3 > 4;\n"),
+            ("a == (b == c)", "a == (b == c);\n"),
+            ("a+add(b*c,(d))", "a + add(b * c, d);\n"),
+            ("(-a)[0] + -a[0]", "(-a)[0] + -a[0];\n"),
+            ("(a + b)(c)", "(a + b)(c);\n"),
+            ("f(x)[0](y)", "f(x)[0](y);\n"),
+            (r#"[1,"two\n",[ ]]"#, "[1, \"two\\n\", []];\n"),
+            (r#"{ "a":1,true:{}}"#, "{\"a\": 1, true: {}};\n"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(format_source(input), expected, "wrong formatting for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        let tests = [
+            ("if(x){1}", "if (x) { 1 }\n"),
+            ("if (x) { return 1 } else { 2; }; 3", "if (x) { return 1; } else { 2 }\n3;\n"),
+            ("if (true) { puts(1) }; -5", "if (true) { puts(1) };\n-5;\n"),
+            ("if (x) { 1 }; [1]", "if (x) { 1 };\n[1];\n"),
+            ("let f = fn() {};", "let f = fn() {};\n"),
+            ("let f = fn() { let x = 1; };", "let f = fn() {\n    let x = 1;\n};\n"),
+            (
+                "let f = fn(a, b) { let c = a + b; if (c > 1) { return c; } c }",
+                "let f = fn(a, b) {\n    let c = a + b;\n    if (c > 1) { return c; }\n    c\n};\n",
+            ),
+            (
+                "let m = macro(x) { quote(unquote(x) * 2) }; map([1], fn(x) { x })",
+                "let m = macro(x) { quote(unquote(x) * 2) };\nmap([1], fn(x) { x });\n",
+            ),
+            (
+                "fn() {\n  let a = 1;\n\n  a\n}()",
+                "fn() {\n    let a = 1;\n\n    a\n}();\n",
+            ),
+            // too long for one line, so both branches are split
+            (
+                "if (ready) { launch(rocket, countdown, [1, 2, 3]) } else { wait(seconds * 1000, retry) }",
+                "if (ready) {\n    launch(rocket, countdown, [1, 2, 3])\n} else {\n    wait(seconds * 1000, retry)\n}\n",
+            ),
+            (
+                "let f = fn(x) { fn(y) { x + y + x + y + x + y + x + y + x + y + x + y + x + y + x } }",
+                "let f = fn(x) {\n    fn(y) { x + y + x + y + x + y + x + y + x + y + x + y + x + y + x }\n};\n",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(format_source(input), expected, "wrong formatting for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let tests = [
+            ("// only a comment", "// only a comment\n"),
+            ("let x = 1;   // one  \n\n\n/* two */\nx", "let x = 1; // one\n\n/* two */\nx;\n"),
+            ("x; /* a */ /* b */ // c\ny", "x; /* a */ /* b */ // c\ny;\n"),
+            ("let f = fn() { /* todo */ };", "let f = fn() {\n    /* todo */\n};\n"),
+            ("if (x) { 1 // one\n} else { 2 }", "if (x) {\n    1 // one\n} else {\n    2\n}\n"),
+            (
+                "let f = fn(a) { // doc\n  a\n\n  // done\n};",
+                "let f = fn(a) {\n    // doc\n    a\n\n    // done\n};\n",
+            ),
+            ("puts(1, /* two */ 2, // three\n 3);\nputs(4)", "puts(\n    1,\n    /* two */ 2, // three\n    3\n);\nputs(4);\n"),
+            // comments inside an expression stay where they were
+            ("puts(x /* inner */ + 1) // end", "puts(x /* inner */ + 1); // end\n"),
+            ("f(/* a */ x)[0 /* b */]", "f(/* a */ x)[0 /* b */];\n"),
+            ("let a = [\n  1,\n  // c\n  2\n];", "let a = [\n    1,\n    // c\n    2\n];\n"),
+            ("let h = {\"a\": 1, // one\n\"b\": 2};", "let h = {\n    \"a\": 1, // one\n    \"b\": 2\n};\n"),
+            ("/* a\n   b */\nx", "/* a\n   b */\nx;\n"),
+        ];
+
+        for (input, expected) in tests {
+            let formatted = format_source(input);
+            assert_eq!(formatted, expected, "wrong formatting for {:?}", input);
+            assert_eq!(format_source(&formatted), formatted, "formatting is not idempotent for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_format_is_idempotent_and_keeps_meaning() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+        let mut sources: Vec<String> = std::fs::read_dir(dir)
+            .expect("should have read tests/programs")
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .filter(|source| Parser::new(Lexer::new(source)).parse_program().is_ok())
+            .collect();
+        sources.push("let x = if (a) { -(-1) } else { fn(y) { y }(2) }; !(x == [1][0]) + {1: 2}[1] * (3 - 4 - (5 - 6))".to_string());
+        sources.push("if (true) { puts(1) }; -5".to_string());
+        sources.push("if (x) { 1 }; [1]".to_string());
+        sources.push("if (x) { 1 } else { 2 }; (3)".to_string());
+        sources.push("fn() { if (x) { 1 }; !y; if (y) { 2 } [3] }".to_string());
+
+        for source in sources {
+            let formatted = format_source(&source);
+            assert_eq!(format_source(&formatted), formatted, "formatting is not idempotent for:\n{}", source);
+            assert_eq!(parse(&formatted).print(), parse(&source).print(), "formatting changed the meaning of:\n{}", source);
+        }
+    }
+}
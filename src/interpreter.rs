@@ -0,0 +1,297 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::builtins::Registry;
+use crate::diagnostics::{self, Diagnostic};
+use crate::environment::{Env, Environment};
+use crate::evaluator;
+use crate::lexer::Lexer;
+use crate::macros;
+use crate::object::{Object, RuntimeError};
+use crate::parser::{ParseError, Parser};
+use crate::value::Value;
+
+/// A Monkey interpreter with its own set of globals. Every call to
+/// [`eval_str`](Interpreter::eval_str) runs in the same global scope, so
+/// bindings made by one script are visible to the next.
+///
+/// Scripts fail with a "stack overflow" error once they nest 1023 function
+/// calls, or sooner once evaluation has used the native stack set by
+/// [`set_max_stack`](Interpreter::set_max_stack).
+///
+/// ```
+/// use monkey_lang::{Interpreter, Value};
+///
+/// let mut interpreter = Interpreter::new();
+/// interpreter.set_global("limit", 10);
+/// interpreter.register_fn("double", |args| match args {
+///     [Value::Integer(n)] => Ok(Value::Integer(n * 2)),
+///     _ => Err("double takes one integer".to_string()),
+/// });
+///
+/// let value = interpreter.eval_str("let big = double(limit); big + 1").unwrap();
+/// assert_eq!(value, Value::Integer(21));
+/// assert_eq!(interpreter.get_global("big"), Some(Value::Integer(20)));
+/// ```
+pub struct Interpreter {
+    env: Env,
+    // where the macros defined so far live; they are expanded before
+    // evaluation and never become globals
+    macros: Env,
+    builtins: Rc<RefCell<Registry>>,
+    max_stack: usize,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let builtins = Rc::new(RefCell::new(Registry::standard()));
+        Interpreter {
+            env: Environment::with_builtins(Rc::clone(&builtins)),
+            macros: Environment::with_builtins(Rc::clone(&builtins)),
+            builtins,
+            max_stack: evaluator::DEFAULT_MAX_STACK,
+        }
+    }
+
+    /// Sets how many bytes of native stack evaluation may use below the
+    /// `eval_str` call, 1 MiB by default so that scripts run safely on a
+    /// spawned thread's default 2 MiB. Raise it on a bigger stack to allow
+    /// deeper recursion.
+    pub fn set_max_stack(&mut self, bytes: usize) {
+        self.max_stack = bytes;
+    }
+
+    /// Parses `source`, expands its macros and evaluates it, returning the
+    /// value of its last statement.
+    pub fn eval_str(&mut self, source: &str) -> Result<Value, Error> {
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program().map_err(Error::Parse)?;
+        evaluator::with_max_stack(self.max_stack, || {
+            let program = macros::expand(program, &self.macros).map_err(Error::Runtime)?;
+
+            match evaluator::eval(&program, &self.env) {
+                Object::Error(err) => Err(Error::Runtime(err)),
+                value => Ok(Value::from_object(&value)),
+            }
+        })
+    }
+
+    pub fn set_global(&mut self, name: &str, value: impl Into<Value>) {
+        self.env.borrow_mut().set(name, value.into().into_object());
+    }
+
+    /// The value bound to `name` in the global scope. Builtins and registered
+    /// natives are not globals and aren't returned here.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.env.borrow().get(name).map(|value| Value::from_object(&value))
+    }
+
+    /// Makes `func` callable from scripts as `name`, alongside builtins such
+    /// as `len`. Like them it can be shadowed by a `let`. An `Err` returned
+    /// by it becomes a runtime error at the call site.
+    pub fn register_fn<F>(&mut self, name: &str, func: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + 'static,
+    {
+        let native = move |args: &[Object]| {
+            let args: Vec<Value> = args.iter().map(Value::from_object).collect();
+            func(&args)
+                .map(Value::into_object)
+                .map_err(|message| RuntimeError::new(Default::default(), message))
+        };
+        self.builtins.borrow_mut().register(name, native);
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+/// Why a script could not be run: it either didn't parse, or failed while
+/// being evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    Parse(Vec<ParseError>),
+    Runtime(RuntimeError),
+}
+
+impl Error {
+    /// Renders the error as diagnostics pointing into `source`, the same way
+    /// the `monkey_lang` binary reports them. `name` is where the source came
+    /// from, such as a file path.
+    pub fn render(&self, name: &str, source: &str) -> String {
+        match self {
+            Error::Parse(errors) => errors.iter()
+                .map(|err| diagnostics::render(&Diagnostic::from(err), name, source, false))
+                .collect(),
+            Error::Runtime(err) => diagnostics::render(&Diagnostic::from(err), name, source, false),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                let errors: Vec<String> = errors.iter()
+                    .map(|err| format!("{}: {}", err.span().start, err))
+                    .collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            Error::Runtime(err) => write!(f, "{}: {}", err.span.start, err.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use crate::object::HashKey;
+
+    #[test]
+    fn test_eval_str_returns_values() {
+        let mut interpreter = Interpreter::new();
+        let tests = [
+            ("1 + 2", Value::Integer(3)),
+            ("1 < 2", Value::Boolean(true)),
+            (r#""mon" + "key""#, Value::String("monkey".to_string())),
+            ("[1, [true]]", Value::Array(vec![Value::Integer(1), Value::Array(vec![Value::Boolean(true)])])),
+            (r#"{"a": 1}"#, Value::Hash([(HashKey::String("a".to_string()), Value::Integer(1))].into())),
+            ("if (false) { 1 }", Value::Null),
+            ("", Value::Null),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(interpreter.eval_str(input), Ok(expected), "wrong value for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_globals_persist_between_scripts() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("let add = fn(a, b) { a + b }; let x = 2;").unwrap();
+
+        assert_eq!(interpreter.eval_str("add(x, 3)"), Ok(Value::Integer(5)));
+        assert_eq!(interpreter.get_global("x"), Some(Value::Integer(2)));
+        assert_eq!(interpreter.get_global("missing"), None);
+        assert_eq!(interpreter.get_global("len"), None);
+        assert!(matches!(interpreter.get_global("add"), Some(Value::Function(_))));
+    }
+
+    #[test]
+    fn test_macros_persist_between_scripts() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval_str("let twice = macro(x) { quote([unquote(x), unquote(x)]) };").unwrap();
+
+        assert_eq!(interpreter.eval_str("twice(1 + 2)"), Ok(Value::from(vec![Value::Integer(3), Value::Integer(3)])));
+        assert_eq!(interpreter.eval_str("quote(1 + unquote(2 * 3))"), Ok(Value::from("(1 + 6)")));
+        assert_eq!(interpreter.get_global("twice"), None);
+
+        let err = interpreter.eval_str("twice()").unwrap_err();
+        assert_eq!(err.to_string(), "1:6: wrong number of arguments: want=1, got=0");
+    }
+
+    #[test]
+    fn test_set_global() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("name", "monkey");
+        interpreter.set_global("ports", vec![Value::Integer(80), Value::Integer(443)]);
+        interpreter.set_global("config", Value::Hash([(HashKey::Boolean(true), Value::from(1))].into()));
+
+        assert_eq!(interpreter.eval_str("len(name) + ports[1] + config[true]"), Ok(Value::Integer(450)));
+
+        // functions can be moved between interpreters
+        let square = Interpreter::new().eval_str("fn(x) { x * x }").unwrap();
+        interpreter.set_global("square", square.clone());
+        assert_eq!(interpreter.eval_str("square(4)"), Ok(Value::Integer(16)));
+        assert_eq!(interpreter.get_global("square"), Some(square));
+    }
+
+    #[test]
+    fn test_register_fn() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.register_fn("count", move |args| {
+            counter.set(counter.get() + 1);
+            Ok(Value::Integer(args.len() as i64))
+        });
+        interpreter.register_fn("fail", |_| Err("service unavailable".to_string()));
+
+        assert_eq!(interpreter.eval_str("count(1, 2) + count()"), Ok(Value::Integer(2)));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(interpreter.get_global("count"), None);
+
+        // registering after a function was defined still reaches it
+        interpreter.eval_str("let f = fn() { late() };").unwrap();
+        interpreter.register_fn("late", |_| Ok(Value::from("here")));
+        assert_eq!(interpreter.eval_str("f()"), Ok(Value::from("here")));
+
+        let err = interpreter.eval_str("1;\nfail()").unwrap_err();
+        assert_eq!(err.to_string(), "2:5: service unavailable");
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut interpreter = Interpreter::new();
+
+        let err = interpreter.eval_str("let x 5; let = 1;").unwrap_err();
+        assert!(matches!(&err, Error::Parse(errors) if errors.len() == 2), "expected two parse errors, got {:?}", err);
+        assert_eq!(err.to_string(), "1:7: expected `=`, found integer\n1:14: expected identifier, found `=`");
+
+        let source = "let x = 5;\nx + true;";
+        let err = interpreter.eval_str(source).unwrap_err();
+        assert!(matches!(err, Error::Runtime(_)), "expected a runtime error, got {:?}", err);
+        assert_eq!(err.render("config.mk", source), concat!(
+            "error[E0100]: type mismatch: INTEGER + BOOLEAN\n",
+            " --> config.mk:2:3\n",
+            "  |\n",
+            "2 | x + true;\n",
+            "  |   ^\n",
+        ));
+    }
+
+    #[test]
+    fn test_runaway_recursion_is_an_error() {
+        // room for the deepest recursion allowed, in a debug build
+        let thread = std::thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(|| {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_max_stack(32 * 1024 * 1024);
+
+            let err = interpreter.eval_str("let f = fn(x) { f(x + 1) }; f(0)").unwrap_err();
+            assert!(matches!(err, Error::Runtime(_)), "expected a runtime error, got {:?}", err);
+            assert_eq!(err.to_string(), "1:18: stack overflow");
+
+            // the depth is back to zero afterwards
+            let deep = "let down = fn(n) { if (n == 0) { 0 } else { 1 + down(n - 1) } }; down(1000)";
+            assert_eq!(interpreter.eval_str(deep), Ok(Value::from(1000)));
+        });
+        thread.unwrap().join().unwrap();
+    }
+
+    #[test]
+    fn test_runaway_recursion_fits_a_default_thread() {
+        let thread = std::thread::spawn(|| {
+            let mut interpreter = Interpreter::new();
+
+            let err = interpreter.eval_str("let f = fn(x) { f(x + 1) }; f(0)").unwrap_err();
+            assert_eq!(err.to_string(), "1:18: stack overflow");
+
+            // calls that each take more stack are stopped sooner
+            let source = "let g = fn(x) { let a = [x, x]; if (true) { if (true) { g(a[0] + 1) } } }; g(0)";
+            let err = interpreter.eval_str(source).unwrap_err();
+            assert_eq!(err.to_string(), "1:58: stack overflow");
+
+            // and macros are expanded under the same limit
+            let source = "let m = macro() { let h = fn() { h() }; h() }; m()";
+            assert!(interpreter.eval_str(source).is_err());
+        });
+        thread.join().unwrap();
+    }
+}
@@ -1,19 +1,63 @@
+use std::fmt;
 use crate::token;
+use crate::unicode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    UnterminatedString { span: token::Span },
+    InvalidEscape { sequence: String, span: token::Span },
+    UnterminatedComment { span: token::Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> token::Span {
+        match self {
+            LexError::UnterminatedString { span }
+            | LexError::InvalidEscape { span, .. }
+            | LexError::UnterminatedComment { span } => *span,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::UnterminatedString { .. } => write!(f, "unterminated string literal"),
+            LexError::InvalidEscape { sequence, .. } => write!(f, "invalid escape sequence `{}` in string", sequence),
+            LexError::UnterminatedComment { .. } => write!(f, "unterminated block comment"),
+        }
+    }
+}
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     read_position: usize,
     ch: char,
+    // where `ch` sits in the source, and where the character after it does
+    location: token::Position,
+    next_location: token::Position,
+    errors: Vec<LexError>,
+    keep_comments: bool,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Lexer {
+        Lexer::new_at(input, Default::default())
+    }
+
+    /// Lexes `input` as if it began at `start` in a larger source, so that
+    /// spans of separately lexed chunks (e.g. REPL lines) don't overlap.
+    pub fn new_at(input: &str, start: token::Position) -> Lexer {
         let mut lexer = Lexer {
             input: input.chars().collect(),
             position: 0,
             read_position: 0,
             ch: Default::default(),
+            location: start,
+            next_location: start,
+            errors: vec![],
+            keep_comments: false,
         };
 
         lexer.read_char();
@@ -21,6 +65,14 @@ impl Lexer {
         lexer
     }
 
+    /// Makes the lexer return comments as [`Comment`](token::TokenKind::Comment)
+    /// tokens instead of skipping them, for tools such as the formatter that
+    /// need to keep them. The parser doesn't accept them.
+    pub fn with_comments(mut self) -> Lexer {
+        self.keep_comments = true;
+        self
+    }
+
     fn read_char(&mut self) {
         if self.read_position >= self.input.len() {
             self.ch = '\0';
@@ -30,50 +82,100 @@ impl Lexer {
 
         self.position = self.read_position;
         self.read_position += 1;
+
+        self.location = self.next_location;
+        if self.ch == '\n' {
+            self.next_location.line += 1;
+            self.next_location.column = 1;
+        } else if self.ch != '\0' {
+            self.next_location.column += 1;
+        }
+        self.next_location.offset += self.ch.len_utf8();
     }
 
     pub fn next_token(&mut self) -> token::Token {
-        self.skip_whitespace();
+        loop {
+            self.skip_whitespace();
+
+            let start = self.location;
+            let mut token = self.read_token();
+            token.span = token::Span { start, end: self.location };
+
+            if token.kind != token::TokenKind::Comment || self.keep_comments {
+                return token;
+            }
+        }
+    }
+
+    /// Errors found since the last call. Lexing carries on past them, so the
+    /// parser collects these as it goes rather than stopping.
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        std::mem::take(&mut self.errors)
+    }
 
+    fn read_token(&mut self) -> token::Token {
         let token = match self.ch {
             '=' =>  {
                 if self.peek_char() == '=' {
                     self.read_char();
-                    token::Token { kind: token::TokenKind::Eq, literal: "==".to_string() }
+                    Lexer::new_token_literal(token::TokenKind::Eq, "==")
                 } else {
                     Lexer::new_token(token::TokenKind::Assign, self.ch)
                 }
             },
             ';' => Lexer::new_token(token::TokenKind::Semicolon, self.ch),
+            ':' => Lexer::new_token(token::TokenKind::Colon, self.ch),
             '(' => Lexer::new_token(token::TokenKind::LParen, self.ch),
             ')' => Lexer::new_token(token::TokenKind::RParen, self.ch),
             ',' => Lexer::new_token(token::TokenKind::Comma, self.ch),
             '+' => Lexer::new_token(token::TokenKind::Plus, self.ch),
             '{' => Lexer::new_token(token::TokenKind::LBrace, self.ch),
             '}' => Lexer::new_token(token::TokenKind::RBrace, self.ch),
-            '\0' => token::Token{ kind: token::TokenKind::EOF, literal: "".to_string() },
+            '[' => Lexer::new_token(token::TokenKind::LBracket, self.ch),
+            ']' => Lexer::new_token(token::TokenKind::RBracket, self.ch),
+            '\0' => {
+                // don't step past the end, so EOF gets an empty span
+                return Lexer::new_token_literal(token::TokenKind::EOF, "");
+            }
             '-' => Lexer::new_token(token::TokenKind::Minus, self.ch),
             '!' => {
                 if self.peek_char() == '=' {
                     self.read_char();
-                    token::Token { kind: token::TokenKind::NotEq, literal: "!=".to_string() }
+                    Lexer::new_token_literal(token::TokenKind::NotEq, "!=")
                 } else {
                     Lexer::new_token(token::TokenKind::Bang, self.ch)
                 }
             }
-            '/' => Lexer::new_token(token::TokenKind::Slash, self.ch),
+            '/' => match self.peek_char() {
+                '/' => return Lexer::new_token_literal(token::TokenKind::Comment, &self.read_line_comment()),
+                '*' => return Lexer::new_token_literal(token::TokenKind::Comment, &self.read_block_comment()),
+                _ => Lexer::new_token(token::TokenKind::Slash, self.ch),
+            },
             '*' => Lexer::new_token(token::TokenKind::Asterisk, self.ch),
             '<' => Lexer::new_token(token::TokenKind::Lt, self.ch),
             '>' => Lexer::new_token(token::TokenKind::Gt, self.ch),
+            '"' => {
+                let start = self.location;
+                let literal = self.read_string();
+                if self.ch == '\0' {
+                    self.errors.push(LexError::UnterminatedString {
+                        span: token::Span { start, end: self.location },
+                    });
+                } else {
+                    // step past the closing quote
+                    self.read_char();
+                }
+                return Lexer::new_token_literal(token::TokenKind::String, &literal);
+            }
             _ => {
-                return if Lexer::is_letter(self.ch) {
+                if Lexer::is_letter(self.ch) {
                     let literal = self.read_identifier();
                     let kind = token::lookup_ident(&literal);
-                    return token::Token { kind: kind, literal: literal }
+                    return token::Token { kind, literal, span: Default::default() }
                 } else if Lexer::is_digit(self.ch) {
                     let literal = self.read_number();
-                    return token::Token { kind: token::TokenKind::Int, literal: literal }
-                }else {
+                    return token::Token { kind: token::TokenKind::Int, literal, span: Default::default() }
+                } else {
                     Lexer::new_token(token::TokenKind::Illegal, self.ch)
                 }
             },
@@ -86,13 +188,134 @@ impl Lexer {
 
 
     fn read_identifier(&mut self) -> String {
-       let mut indentifier = String::new();
-        while Lexer::is_letter(self.ch) {
-            indentifier.push(self.ch);
+        let mut identifier = String::new();
+        while unicode::is_xid_continue(self.ch) {
+            identifier.push(self.ch);
+            self.read_char();
+        }
+
+        identifier
+    }
+
+    // Reads a `//` comment up to the end of its line, leaving `ch` on the
+    // newline.
+    fn read_line_comment(&mut self) -> String {
+        let mut comment = String::new();
+        while self.ch != '\n' && self.ch != '\0' {
+            comment.push(self.ch);
+            self.read_char();
+        }
+
+        comment
+    }
+
+    // Reads a `/* ... */` comment, which may nest, leaving `ch` just past
+    // its end.
+    fn read_block_comment(&mut self) -> String {
+        let start = self.location;
+        let mut comment = String::new();
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('\0', _) => {
+                    self.errors.push(LexError::UnterminatedComment {
+                        span: token::Span { start, end: self.location },
+                    });
+                    break;
+                }
+                ('/', '*') => depth += 1,
+                ('*', '/') => depth -= 1,
+                (ch, _) => {
+                    comment.push(ch);
+                    self.read_char();
+                    continue;
+                }
+            }
+
+            // both characters of a delimiter
+            comment.push(self.ch);
+            self.read_char();
+            comment.push(self.ch);
+            self.read_char();
+            if depth == 0 {
+                break;
+            }
+        }
+
+        comment
+    }
+
+    // Reads the contents of a string literal, leaving `ch` on the closing
+    // quote, or on EOF if there isn't one.
+    fn read_string(&mut self) -> String {
+        let mut string = String::new();
+
+        loop {
+            self.read_char();
+            match self.ch {
+                '"' | '\0' => break,
+                '\\' => {
+                    let start = self.location;
+                    self.read_char();
+                    match self.ch {
+                        'n' => string.push('\n'),
+                        't' => string.push('\t'),
+                        'r' => string.push('\r'),
+                        '"' => string.push('"'),
+                        '\\' => string.push('\\'),
+                        'u' => match self.read_unicode_escape() {
+                            Ok(ch) => string.push(ch),
+                            Err(sequence) => self.errors.push(LexError::InvalidEscape {
+                                sequence,
+                                span: token::Span { start, end: self.next_location },
+                            }),
+                        },
+                        '\0' => break,
+                        other => self.errors.push(LexError::InvalidEscape {
+                            sequence: format!("\\{}", other),
+                            span: token::Span { start, end: self.next_location },
+                        }),
+                    }
+                }
+                ch => string.push(ch),
+            }
+        }
+
+        string
+    }
+
+    // Reads the `{...}` of a `\u{...}` escape with `ch` on the `u`. On
+    // failure returns the malformed sequence, having consumed only the part
+    // that belongs to it.
+    fn read_unicode_escape(&mut self) -> Result<char, String> {
+        let mut sequence = "\\u".to_string();
+
+        if self.peek_char() != '{' {
+            return Err(sequence);
+        }
+        self.read_char();
+        sequence.push('{');
+
+        let mut digits = String::new();
+        while self.peek_char().is_ascii_hexdigit() {
             self.read_char();
+            digits.push(self.ch);
         }
+        sequence.push_str(&digits);
 
-       indentifier
+        if self.peek_char() != '}' {
+            return Err(sequence);
+        }
+        self.read_char();
+        sequence.push('}');
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(sequence);
+        }
+        u32::from_str_radix(&digits, 16).ok()
+            .and_then(char::from_u32)
+            .ok_or(sequence)
     }
 
     fn skip_whitespace(&mut self) {
@@ -121,26 +344,28 @@ impl Lexer {
     }
 
     fn is_digit(ch: char) -> bool {
-        '0' <= ch && ch <= '9'
+        ch.is_ascii_digit()
     }
 
+    // Identifiers follow Unicode's XID rules, except that they may also
+    // start with `_`.
     fn is_letter(ch: char) -> bool {
-        Lexer::is_alphabetic(ch) || ch == '_'
+        unicode::is_xid_start(ch) || ch == '_'
     }
 
-    fn is_alphabetic(ch: char) -> bool {
-        'a' <= ch && ch <= 'z' || 'A' <= ch && ch <= 'Z'
+    fn new_token(kind: token::TokenKind, ch: char) -> token::Token {
+        token::Token { kind, literal: ch.to_string(), span: Default::default() }
     }
 
-    fn new_token(kind: token::TokenKind, ch: char) -> token::Token {
-        token::Token { kind: kind, literal: ch.to_string() }
+    fn new_token_literal(kind: token::TokenKind, literal: &str) -> token::Token {
+        token::Token { kind, literal: literal.to_string(), span: Default::default() }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::lexer::Lexer;
-    use crate::token::{Token, TokenKind};
+    use crate::lexer::{LexError, Lexer};
+    use crate::token::{Position, Span, Token, TokenKind};
 
 
     #[test]
@@ -154,7 +379,7 @@ mod test {
         };
 
         let result = add(five, ten);
-        !-/*5;
+        !-/ *5;
         5 < 10 > 5;
 
         if (5 < 10) {
@@ -165,89 +390,120 @@ mod test {
 
         10 == 10;
         10 != 9;
+        [1, 2];
+        {"foo": "bar"}
+        macro(x, y) { x + y; };
         "#;
 
         let expected = vec![
-            Token { kind: TokenKind::Let, literal: "let".to_string() },
-            Token { kind: TokenKind::Ident, literal: "five".to_string() },
-            Token { kind: TokenKind::Assign, literal: "=".to_string() },
-            Token { kind: TokenKind::Int, literal: "5".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::Let, literal: "let".to_string() },
-            Token { kind: TokenKind::Ident, literal: "ten".to_string() },
-            Token { kind: TokenKind::Assign, literal: "=".to_string() },
-            Token { kind: TokenKind::Int, literal: "10".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::Let, literal: "let".to_string() },
-            Token { kind: TokenKind::Ident, literal: "add".to_string() },
-            Token { kind: TokenKind::Assign, literal: "=".to_string() },
-            Token { kind: TokenKind::Function, literal: "fn".to_string() },
-            Token { kind: TokenKind::LParen, literal: "(".to_string() },
-            Token { kind: TokenKind::Ident, literal: "x".to_string() },
-            Token { kind: TokenKind::Comma, literal: ",".to_string() },
-            Token { kind: TokenKind::Ident, literal: "y".to_string() },
-            Token { kind: TokenKind::RParen, literal: ")".to_string() },
-            Token { kind: TokenKind::LBrace, literal: "{".to_string() },
-            Token { kind: TokenKind::Ident, literal: "x".to_string() },
-            Token { kind: TokenKind::Plus, literal: "+".to_string() },
-            Token { kind: TokenKind::Ident, literal: "y".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-            Token { kind: TokenKind::RBrace, literal: "}".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::Let, literal: "let".to_string() },
-            Token { kind: TokenKind::Ident, literal: "result".to_string() },
-            Token { kind: TokenKind::Assign, literal: "=".to_string() },
-            Token { kind: TokenKind::Ident, literal: "add".to_string() },
-            Token { kind: TokenKind::LParen, literal: "(".to_string() },
-            Token { kind: TokenKind::Ident, literal: "five".to_string() },
-            Token { kind: TokenKind::Comma, literal: ",".to_string() },
-            Token { kind: TokenKind::Ident, literal: "ten".to_string() },
-            Token { kind: TokenKind::RParen, literal: ")".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::Bang, literal: "!".to_string() },
-            Token { kind: TokenKind::Minus, literal: "-".to_string() },
-            Token { kind: TokenKind::Slash, literal: "/".to_string() },
-            Token { kind: TokenKind::Asterisk, literal: "*".to_string() },
-            Token { kind: TokenKind::Int, literal: "5".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::Int, literal: "5".to_string() },
-            Token { kind: TokenKind::Lt, literal: "<".to_string() },
-            Token { kind: TokenKind::Int, literal: "10".to_string() },
-            Token { kind: TokenKind::Gt, literal: ">".to_string() },
-            Token { kind: TokenKind::Int, literal: "5".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::If, literal: "if".to_string() },
-            Token { kind: TokenKind::LParen, literal: "(".to_string() },
-            Token { kind: TokenKind::Int, literal: "5".to_string() },
-            Token { kind: TokenKind::Lt, literal: "<".to_string() },
-            Token { kind: TokenKind::Int, literal: "10".to_string() },
-            Token { kind: TokenKind::RParen, literal: ")".to_string() },
-            Token { kind: TokenKind::LBrace, literal: "{".to_string() },
-            Token { kind: TokenKind::Return, literal: "return".to_string() },
-            Token { kind: TokenKind::True, literal: "true".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-            Token { kind: TokenKind::RBrace, literal: "}".to_string() },
-            Token { kind: TokenKind::Else, literal: "else".to_string() },
-            Token { kind: TokenKind::LBrace, literal: "{".to_string() },
-            Token { kind: TokenKind::Return, literal: "return".to_string() },
-            Token { kind: TokenKind::False, literal: "false".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-            Token { kind: TokenKind::RBrace, literal: "}".to_string() },
-
-            Token { kind: TokenKind::Int, literal: "10".to_string() },
-            Token { kind: TokenKind::Eq, literal: "==".to_string() },
-            Token { kind: TokenKind::Int, literal: "10".to_string() },
-            Token { kind: TokenKind::Semicolon, literal: ";".to_string() },
-
-            Token { kind: TokenKind::Int, literal: "10".to_string() },
-            Token { kind: TokenKind::NotEq, literal: "!=".to_string() },
-            Token { kind: TokenKind::Int, literal: "9".to_string() },
+            Token { kind: TokenKind::Let, literal: "let".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "five".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Assign, literal: "=".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "5".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Let, literal: "let".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "ten".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Assign, literal: "=".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "10".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Let, literal: "let".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "add".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Assign, literal: "=".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Function, literal: "fn".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LParen, literal: "(".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "x".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Comma, literal: ",".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "y".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RParen, literal: ")".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LBrace, literal: "{".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "x".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Plus, literal: "+".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "y".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RBrace, literal: "}".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Let, literal: "let".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "result".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Assign, literal: "=".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "add".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LParen, literal: "(".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "five".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Comma, literal: ",".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "ten".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RParen, literal: ")".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Bang, literal: "!".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Minus, literal: "-".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Slash, literal: "/".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Asterisk, literal: "*".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "5".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Int, literal: "5".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Lt, literal: "<".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "10".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Gt, literal: ">".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "5".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::If, literal: "if".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LParen, literal: "(".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "5".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Lt, literal: "<".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "10".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RParen, literal: ")".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LBrace, literal: "{".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Return, literal: "return".to_string(), ..Default::default() },
+            Token { kind: TokenKind::True, literal: "true".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RBrace, literal: "}".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Else, literal: "else".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LBrace, literal: "{".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Return, literal: "return".to_string(), ..Default::default() },
+            Token { kind: TokenKind::False, literal: "false".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RBrace, literal: "}".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Int, literal: "10".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Eq, literal: "==".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "10".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Int, literal: "10".to_string(), ..Default::default() },
+            Token { kind: TokenKind::NotEq, literal: "!=".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "9".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::LBracket, literal: "[".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "1".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Comma, literal: ",".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Int, literal: "2".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RBracket, literal: "]".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LBrace, literal: "{".to_string(), ..Default::default() },
+            Token { kind: TokenKind::String, literal: "foo".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Colon, literal: ":".to_string(), ..Default::default() },
+            Token { kind: TokenKind::String, literal: "bar".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RBrace, literal: "}".to_string(), ..Default::default() },
+
+            Token { kind: TokenKind::Macro, literal: "macro".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LParen, literal: "(".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "x".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Comma, literal: ",".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "y".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RParen, literal: ")".to_string(), ..Default::default() },
+            Token { kind: TokenKind::LBrace, literal: "{".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "x".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Plus, literal: "+".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Ident, literal: "y".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+            Token { kind: TokenKind::RBrace, literal: "}".to_string(), ..Default::default() },
+            Token { kind: TokenKind::Semicolon, literal: ";".to_string(), ..Default::default() },
+            Token { kind: TokenKind::EOF, literal: "".to_string(), ..Default::default() },
 
         ];
 
@@ -260,4 +516,142 @@ mod test {
             assert_eq!(exp.literal, recv_token.literal, "tests[{}] - literal wrong. expected={}, got={}", idx, exp.literal, recv_token.literal)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_token_spans() {
+        let input = "let x = 5;\n  x == 10;";
+
+        let pos = |line, column, offset| Position { line, column, offset };
+        let expected = vec![
+            (TokenKind::Let, pos(1, 1, 0), pos(1, 4, 3)),
+            (TokenKind::Ident, pos(1, 5, 4), pos(1, 6, 5)),
+            (TokenKind::Assign, pos(1, 7, 6), pos(1, 8, 7)),
+            (TokenKind::Int, pos(1, 9, 8), pos(1, 10, 9)),
+            (TokenKind::Semicolon, pos(1, 10, 9), pos(1, 11, 10)),
+            (TokenKind::Ident, pos(2, 3, 13), pos(2, 4, 14)),
+            (TokenKind::Eq, pos(2, 5, 15), pos(2, 7, 17)),
+            (TokenKind::Int, pos(2, 8, 18), pos(2, 10, 20)),
+            (TokenKind::Semicolon, pos(2, 10, 20), pos(2, 11, 21)),
+            (TokenKind::EOF, pos(2, 11, 21), pos(2, 11, 21)),
+        ];
+
+        let mut l = Lexer::new(input);
+
+        for (idx, (kind, start, end)) in expected.into_iter().enumerate() {
+            let recv_token = l.next_token();
+            assert_eq!(kind, recv_token.kind, "tests[{}] - tokentype wrong. expected={}, got={}", idx, kind, recv_token.kind);
+            assert_eq!(Span { start, end }, recv_token.span, "tests[{}] - span wrong for {}", idx, recv_token.literal);
+        }
+    }
+
+    #[test]
+    fn test_byte_offsets_after_multibyte_characters() {
+        let mut l = Lexer::new("é\n; ;");
+
+        assert_eq!(l.next_token().kind, TokenKind::Ident);
+        let semicolon = l.next_token();
+        assert_eq!(semicolon.span.start, Position { line: 2, column: 1, offset: 3 });
+        let semicolon = l.next_token();
+        assert_eq!(semicolon.span.start, Position { line: 2, column: 3, offset: 5 });
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let mut l = Lexer::new("let größe = 数量1 + x1_y;\n_2 e\u{301} 😀");
+
+        let pos = |line, column, offset| Position { line, column, offset };
+        let expected = vec![
+            (TokenKind::Let, "let", pos(1, 1, 0)),
+            (TokenKind::Ident, "größe", pos(1, 5, 4)),
+            (TokenKind::Assign, "=", pos(1, 11, 12)),
+            (TokenKind::Ident, "数量1", pos(1, 13, 14)),
+            (TokenKind::Plus, "+", pos(1, 17, 22)),
+            (TokenKind::Ident, "x1_y", pos(1, 19, 24)),
+            (TokenKind::Semicolon, ";", pos(1, 23, 28)),
+            (TokenKind::Ident, "_2", pos(2, 1, 30)),
+            (TokenKind::Ident, "e\u{301}", pos(2, 4, 33)),
+            (TokenKind::Illegal, "😀", pos(2, 7, 37)),
+            (TokenKind::EOF, "", pos(2, 8, 41)),
+        ];
+
+        for (idx, (kind, literal, start)) in expected.into_iter().enumerate() {
+            let recv_token = l.next_token();
+            assert_eq!(kind, recv_token.kind, "tests[{}] - tokentype wrong. expected={}, got={}", idx, kind, recv_token.kind);
+            assert_eq!(literal, recv_token.literal, "tests[{}] - literal wrong", idx);
+            assert_eq!(start, recv_token.span.start, "tests[{}] - position wrong for {}", idx, literal);
+        }
+    }
+
+    #[test]
+    fn test_string_tokens() {
+        let input = r#""foobar" "foo bar" "" "tab\there" "say \"hi\"" "back\\slash" "\u{41}\u{1F600}" "é""#;
+
+        let expected = ["foobar", "foo bar", "", "tab\there", "say \"hi\"", "back\\slash", "A\u{1F600}", "é"];
+
+        let mut l = Lexer::new(input);
+
+        for (idx, exp) in expected.into_iter().enumerate() {
+            let recv_token = l.next_token();
+            assert_eq!(TokenKind::String, recv_token.kind, "tests[{}] - tokentype wrong. got={}", idx, recv_token.kind);
+            assert_eq!(exp, recv_token.literal, "tests[{}] - literal wrong", idx);
+        }
+        assert_eq!(l.next_token().kind, TokenKind::EOF);
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let mut l = Lexer::new(r#"let s = "a\qb\u{d800}"; "open"#);
+        let kinds: Vec<TokenKind> = std::iter::from_fn(|| Some(l.next_token()))
+            .map(|t| t.kind)
+            .take_while(|k| *k != TokenKind::EOF)
+            .collect();
+
+        assert_eq!(kinds, [TokenKind::Let, TokenKind::Ident, TokenKind::Assign, TokenKind::String, TokenKind::Semicolon, TokenKind::String]);
+
+        let pos = |column, offset| Position { line: 1, column, offset };
+        assert_eq!(l.take_errors(), [
+            LexError::InvalidEscape { sequence: "\\q".to_string(), span: Span { start: pos(11, 10), end: pos(13, 12) } },
+            LexError::InvalidEscape { sequence: "\\u{d800}".to_string(), span: Span { start: pos(14, 13), end: pos(22, 21) } },
+            LexError::UnterminatedString { span: Span { start: pos(25, 24), end: pos(30, 29) } },
+        ]);
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "let a = 1; // one\n/* two /* nested */ still two */ a / b // last";
+
+        let mut l = Lexer::new(input);
+        let kinds: Vec<TokenKind> = std::iter::from_fn(|| Some(l.next_token()))
+            .map(|t| t.kind)
+            .take_while(|k| *k != TokenKind::EOF)
+            .collect();
+        assert_eq!(kinds, [TokenKind::Let, TokenKind::Ident, TokenKind::Assign, TokenKind::Int, TokenKind::Semicolon, TokenKind::Ident, TokenKind::Slash, TokenKind::Ident]);
+
+        let mut l = Lexer::new(input).with_comments();
+        let comments: Vec<(String, Span)> = std::iter::from_fn(|| Some(l.next_token()))
+            .take_while(|t| t.kind != TokenKind::EOF)
+            .filter(|t| t.kind == TokenKind::Comment)
+            .map(|t| (t.literal, t.span))
+            .collect();
+
+        let pos = |line, column, offset| Position { line, column, offset };
+        assert_eq!(comments, [
+            ("// one".to_string(), Span { start: pos(1, 12, 11), end: pos(1, 18, 17) }),
+            ("/* two /* nested */ still two */".to_string(), Span { start: pos(2, 1, 18), end: pos(2, 33, 50) }),
+            ("// last".to_string(), Span { start: pos(2, 40, 57), end: pos(2, 47, 64) }),
+        ]);
+        assert!(l.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_comment() {
+        let mut l = Lexer::new("1 /* a /* b */\n*");
+
+        assert_eq!(l.next_token().kind, TokenKind::Int);
+        assert_eq!(l.next_token().kind, TokenKind::EOF);
+        assert_eq!(l.take_errors(), [LexError::UnterminatedComment {
+            span: Span { start: Position { line: 1, column: 3, offset: 2 }, end: Position { line: 2, column: 2, offset: 16 } },
+        }]);
+    }
+}
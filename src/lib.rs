@@ -0,0 +1,35 @@
+//! An interpreter for the Monkey programming language.
+//!
+//! [`Interpreter`] is the way to run Monkey from Rust: it evaluates scripts,
+//! exchanges globals with them as [`Value`]s and lets them call back into
+//! native functions.
+
+pub mod token;
+mod unicode;
+mod lexer;
+mod ast;
+mod visit;
+mod parser;
+mod object;
+mod environment;
+mod evaluator;
+mod macros;
+mod formatter;
+mod builtins;
+mod diagnostics;
+mod code;
+mod symbol_table;
+mod compiler;
+mod vm;
+mod value;
+mod interpreter;
+
+// the front ends of the `monkey_lang` binary
+pub mod repl;
+pub mod runner;
+
+pub use interpreter::{Error, Interpreter};
+pub use lexer::LexError;
+pub use object::{HashKey, RuntimeError};
+pub use parser::ParseError;
+pub use value::{FunctionRef, Value};
@@ -0,0 +1,365 @@
+use std::rc::Rc;
+use crate::ast::{ArrayLiteral, BooleanLiteral, CallExpression, ExpressionNode, HashLiteral, IntegerLiteral, LetStatement, MacroLiteral, Program, StatementNode, StringLiteral};
+use crate::environment::{Env, Environment};
+use crate::evaluator;
+use crate::object::{Macro, Object, RuntimeError};
+use crate::token::{Span, Token, TokenKind};
+use crate::visit::{walk_expression_fold, Fold, Visitor};
+
+pub(crate) const MISPLACED_MACRO: &str = "macros can only be defined by a top-level let";
+
+/// Takes the macro definitions out of `program`, storing them in `macros`,
+/// then replaces every call to a macro with the code it returns. Macros
+/// defined by earlier programs in the same `macros` environment can be
+/// called too, which is how the REPL keeps them between lines.
+pub fn expand(mut program: Program, macros: &Env) -> Result<Program, RuntimeError> {
+    define_macros(&mut program, macros);
+
+    let mut misplaced = MisplacedMacro(None);
+    misplaced.visit_program(&program);
+    if let Some(span) = misplaced.0 {
+        return Err(RuntimeError::new(span, MISPLACED_MACRO.to_string()));
+    }
+
+    let mut expander = Expander { macros, error: None };
+    let program = expander.fold_program(program);
+    match expander.error {
+        Some(err) => Err(err),
+        None => Ok(program),
+    }
+}
+
+fn define_macros(program: &mut Program, macros: &Env) {
+    program.statements.retain(|statement| match statement {
+        StatementNode::Let(LetStatement { name, value: Some(ExpressionNode::Macro(mac)), .. }) => {
+            let mac = Macro { parameters: mac.parameters.clone(), body: mac.body.clone() };
+            macros.borrow_mut().set(&name.value, Object::Macro(Rc::new(mac)));
+            false
+        }
+        _ => true,
+    });
+}
+
+/// Finds the first macro literal left once the definitions are taken out.
+struct MisplacedMacro(Option<Span>);
+
+impl Visitor for MisplacedMacro {
+    fn visit_macro_literal(&mut self, mac: &MacroLiteral) {
+        self.0.get_or_insert(mac.token.span);
+    }
+}
+
+/// Replaces macro calls with their expansions, innermost first, so a macro
+/// gets the already expanded code of any macro calls among its arguments.
+struct Expander<'a> {
+    macros: &'a Env,
+    // the first error stops any further expansion
+    error: Option<RuntimeError>,
+}
+
+impl Fold for Expander<'_> {
+    fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+        let expression = walk_expression_fold(self, expression);
+        if self.error.is_some() {
+            return expression;
+        }
+
+        let ExpressionNode::Call(call) = &expression else {
+            return expression;
+        };
+        let Some(mac) = macro_called(call, self.macros) else {
+            return expression;
+        };
+        match expand_call(call, &mac, self.macros) {
+            Ok(expanded) => expanded,
+            Err(err) => {
+                self.error = Some(err);
+                expression
+            }
+        }
+    }
+}
+
+fn macro_called(call: &CallExpression, macros: &Env) -> Option<Rc<Macro>> {
+    let ExpressionNode::IdentifierNode(ident) = call.function.as_ref() else {
+        return None;
+    };
+
+    match macros.borrow().get(&ident.value) {
+        Some(Object::Macro(mac)) => Some(mac),
+        _ => None,
+    }
+}
+
+/// Runs the macro's body with its parameters bound to the quoted arguments
+/// and returns the code it quoted in turn.
+fn expand_call(call: &CallExpression, mac: &Macro, macros: &Env) -> Result<ExpressionNode, RuntimeError> {
+    if mac.parameters.len() != call.arguments.len() {
+        return Err(RuntimeError::new(call.token.span, format!(
+            "wrong number of arguments: want={}, got={}",
+            mac.parameters.len(),
+            call.arguments.len()
+        )));
+    }
+
+    let env = Environment::new_enclosed(macros);
+    for (param, arg) in mac.parameters.iter().zip(&call.arguments) {
+        env.borrow_mut().set(&param.value, Object::Quote(Rc::new(arg.clone())));
+    }
+
+    match evaluator::eval_block_statement(&mac.body, &env) {
+        Object::ReturnValue(value) => match *value {
+            Object::Quote(node) => Ok(Rc::unwrap_or_clone(node)),
+            other => Err(not_quoted(call, &other)),
+        },
+        Object::Quote(node) => Ok(Rc::unwrap_or_clone(node)),
+        Object::Error(err) => Err(err),
+        other => Err(not_quoted(call, &other)),
+    }
+}
+
+fn not_quoted(call: &CallExpression, result: &Object) -> RuntimeError {
+    RuntimeError::new(call.token.span, format!("macro must return a quote, got {}", result.type_name()))
+}
+
+/// Evaluates `quote(x)`: the code `x`, with every `unquote(y)` in it
+/// replaced by the code for the value of `y`.
+pub(crate) fn quote(call: &CallExpression, env: &Env) -> Object {
+    if call.arguments.len() != 1 {
+        return Object::Error(RuntimeError::new(call.token.span, format!(
+            "wrong number of arguments: want=1, got={}",
+            call.arguments.len()
+        )));
+    }
+
+    let mut unquoter = Unquoter { env, error: None };
+    let quoted = unquoter.fold_expression(call.arguments[0].clone());
+    match unquoter.error {
+        Some(err) => Object::Error(err),
+        None => Object::Quote(Rc::new(quoted)),
+    }
+}
+
+/// Splices the values of `unquote` calls into quoted code.
+struct Unquoter<'a> {
+    env: &'a Env,
+    // the first error stops any further evaluation
+    error: Option<RuntimeError>,
+}
+
+impl Fold for Unquoter<'_> {
+    fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+        match expression {
+            ExpressionNode::Call(call) if self.error.is_none() && is_call_to(&call, "unquote") && call.arguments.len() == 1 => {
+                let value = match evaluator::eval_expression(&call.arguments[0], self.env) {
+                    Object::Error(err) => Err(err),
+                    value => object_to_node(&value, call.token.span),
+                };
+                value.unwrap_or_else(|err| {
+                    self.error = Some(err);
+                    ExpressionNode::Call(call)
+                })
+            }
+            other => walk_expression_fold(self, other),
+        }
+    }
+}
+
+pub(crate) fn is_call_to(call: &CallExpression, name: &str) -> bool {
+    matches!(call.function.as_ref(), ExpressionNode::IdentifierNode(ident) if ident.value == name)
+}
+
+/// The code that evaluates to `object`, for splicing a value into quoted
+/// code. The new nodes take `span`, so errors in them point at the
+/// `unquote`. Null and functions have no literal to turn into.
+fn object_to_node(object: &Object, span: Span) -> Result<ExpressionNode, RuntimeError> {
+    let token = |kind: TokenKind, literal: String| Token { kind, literal, span };
+
+    Ok(match object {
+        Object::Integer(value) => ExpressionNode::Integer(IntegerLiteral {
+            token: token(TokenKind::Int, value.to_string()),
+            value: *value,
+        }),
+        Object::Boolean(value) => ExpressionNode::Boolean(BooleanLiteral {
+            token: token(if *value { TokenKind::True } else { TokenKind::False }, value.to_string()),
+            value: *value,
+        }),
+        Object::String(value) => ExpressionNode::String(StringLiteral {
+            token: token(TokenKind::String, value.clone()),
+            value: value.clone(),
+        }),
+        Object::Array(elements) => ExpressionNode::Array(ArrayLiteral {
+            token: token(TokenKind::LBracket, "[".to_string()),
+            elements: elements.iter().map(|element| object_to_node(element, span)).collect::<Result<_, _>>()?,
+        }),
+        Object::Hash(pairs) => ExpressionNode::Hash(HashLiteral {
+            token: token(TokenKind::LBrace, "{".to_string()),
+            pairs: pairs.values()
+                .map(|pair| Ok((object_to_node(&pair.key, span)?, object_to_node(&pair.value, span)?)))
+                .collect::<Result<_, _>>()?,
+        }),
+        Object::Quote(node) => node.as_ref().clone(),
+        other => return Err(RuntimeError::new(span, format!("unusable in unquote: {}", other.type_name()))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Node;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        Parser::new(Lexer::new(input)).parse_program().expect("input should parse")
+    }
+
+    fn eval(input: &str) -> Object {
+        let program = expand(parse(input), &Environment::new()).expect("macros should expand");
+        evaluator::eval(&program, &Environment::new())
+    }
+
+    #[test]
+    fn test_quote() {
+        let tests = [
+            ("quote(5)", "5"),
+            ("quote(5 + 8)", "(5 + 8)"),
+            ("quote(foobar)", "foobar"),
+            ("quote(foobar + barfoo)", "(foobar + barfoo)"),
+            ("quote(fn(x) { x })", "fn(x) { x; }"),
+        ];
+
+        for (input, expected) in tests {
+            match eval(input) {
+                Object::Quote(node) => assert_eq!(node.print(), expected, "wrong quote for {}", input),
+                other => panic!("expected Quote for {}. got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_quote_unquote() {
+        let tests = [
+            ("quote(unquote(4))", "4"),
+            ("quote(unquote(4 + 4))", "8"),
+            ("quote(8 + unquote(4 + 4))", "(8 + 8)"),
+            ("quote(unquote(4 + 4) + 8)", "(8 + 8)"),
+            ("let foobar = 8; quote(foobar)", "foobar"),
+            ("let foobar = 8; quote(unquote(foobar))", "8"),
+            ("quote(unquote(true))", "true"),
+            ("quote(unquote(true == false))", "false"),
+            ("quote(unquote(-3) * 2)", "(-3 * 2)"),
+            (r#"quote(unquote("a" + "b"))"#, r#""ab""#),
+            ("quote(unquote([1, [2]]))", "[1, [2]]"),
+            (r#"quote(unquote({"a": 1, true: [2]}))"#, r#"{true: [2], "a": 1}"#),
+            ("quote(unquote(quote(4 + 4)))", "(4 + 4)"),
+            ("let quotedInfix = quote(4 + 4); quote(unquote(4 + 4) + unquote(quotedInfix))", "(8 + (4 + 4))"),
+            ("quote(unquote(1, 2))", "unquote(1, 2)"),
+        ];
+
+        for (input, expected) in tests {
+            match eval(input) {
+                Object::Quote(node) => assert_eq!(node.print(), expected, "wrong quote for {}", input),
+                other => panic!("expected Quote for {}. got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_define_macros() {
+        let mut program = parse("let number = 1; let function = fn(x, y) { x + y }; let mymacro = macro(x, y) { x + y; };");
+        let env = Environment::new();
+        define_macros(&mut program, &env);
+
+        assert_eq!(program.statements.len(), 2);
+        assert!(env.borrow().get("number").is_none());
+        assert!(env.borrow().get("function").is_none());
+
+        let mymacro = env.borrow().get("mymacro");
+        match mymacro {
+            Some(Object::Macro(mac)) => {
+                let params: Vec<&str> = mac.parameters.iter().map(|p| p.value.as_str()).collect();
+                assert_eq!(params, ["x", "y"]);
+                assert_eq!(mac.body.print(), "{ (x + y); }");
+            }
+            other => panic!("mymacro is not a macro. got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_expand_macros() {
+        let tests = [
+            ("let infixExpression = macro() { quote(1 + 2); }; infixExpression();", "(1 + 2)"),
+            ("let reverse = macro(a, b) { quote(unquote(b) - unquote(a)); }; reverse(2 + 2, 10 - 5);", "((10 - 5) - (2 + 2))"),
+            (r#"
+            let unless = macro(condition, consequence, alternative) {
+                quote(if (!(unquote(condition))) {
+                    unquote(consequence);
+                } else {
+                    unquote(alternative);
+                });
+            };
+            unless(10 > 5, puts("not greater"), puts("greater"));
+            "#, r#"if ((!(10 > 5))) { puts("not greater"); } else { puts("greater"); }"#),
+            ("let early = macro(x) { return quote(unquote(x) * 2); 1 }; fn() { early(3) }", "fn() { (3 * 2); }"),
+            ("let twice = macro(x) { quote([unquote(x), unquote(x)]) }; let a = twice(twice(1));", "let a = [[1, 1], [1, 1]];"),
+        ];
+
+        for (input, expected) in tests {
+            let program = expand(parse(input), &Environment::new()).expect("macros should expand");
+            assert_eq!(program.print(), expected, "wrong expansion for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_macros_persist_between_programs() {
+        let env = Environment::new();
+        expand(parse("let double = macro(x) { quote(unquote(x) * 2) };"), &env).unwrap();
+
+        let program = expand(parse("double(4)"), &env).unwrap();
+        assert_eq!(program.print(), "(4 * 2)");
+    }
+
+    #[test]
+    fn test_macro_errors() {
+        let tests = [
+            ("let m = macro(x) { x }; m()", "wrong number of arguments: want=1, got=0", "1:26"),
+            ("let m = macro() { 1 }; m()", "macro must return a quote, got INTEGER", "1:25"),
+            ("let m = macro() { return 1; }; m()", "macro must return a quote, got INTEGER", "1:33"),
+            ("let m = macro() { 1 + true }; m()", "type mismatch: INTEGER + BOOLEAN", "1:21"),
+            ("fn() { let m = macro() { 1 }; }", MISPLACED_MACRO, "1:16"),
+            ("puts(macro() { 1 })", MISPLACED_MACRO, "1:6"),
+        ];
+
+        for (input, message, position) in tests {
+            match expand(parse(input), &Environment::new()) {
+                Err(err) => {
+                    assert_eq!(err.message, message, "wrong error message for {}", input);
+                    assert_eq!(err.span.start.to_string(), position, "wrong error position for {}", input);
+                }
+                Ok(program) => panic!("no error for {}. got {}", input, program.print()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_quote_errors() {
+        let tests = [
+            ("quote()", "wrong number of arguments: want=1, got=0", "1:6"),
+            ("quote(unquote(1 + true))", "type mismatch: INTEGER + BOOLEAN", "1:17"),
+            ("quote(unquote(fn(x) { x }))", "unusable in unquote: FUNCTION", "1:14"),
+            ("quote(unquote([1, len]))", "unusable in unquote: BUILTIN", "1:14"),
+            ("quote(unquote(puts()))", "unusable in unquote: NULL", "1:14"),
+        ];
+
+        for (input, message, position) in tests {
+            match eval(input) {
+                Object::Error(err) => {
+                    assert_eq!(err.message, message, "wrong error message for {}", input);
+                    assert_eq!(err.span.start.to_string(), position, "wrong error position for {}", input);
+                }
+                other => panic!("no error for {}. got {:?}", input, other),
+            }
+        }
+    }
+}
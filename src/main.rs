@@ -1,11 +1,141 @@
-pub mod token;
-mod lexer;
-mod repl;
-mod ast;
-mod parser;
+use std::io::{IsTerminal, Read};
+use std::process::ExitCode;
+use monkey_lang::runner::{self, Engine};
+use monkey_lang::repl;
 
-fn main() {
-    println!("Hello, this is the Monkey programming language!");
+const USAGE: &str = concat!(
+    "usage: monkey_lang [--engine=eval|vm] [--disassemble] [path/to/script.mk | -]\n",
+    "       monkey_lang fmt [--check] (path/to/script.mk... | -)",
+);
 
-    repl::start(std::io::stdin(), std::io::stdout());
+fn main() -> ExitCode {
+    std::thread::Builder::new()
+        .stack_size(runner::STACK_SIZE)
+        .spawn(run)
+        .expect("failed to start the main thread")
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+fn run() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(("fmt", paths)) = args.split_first().map(|(command, rest)| (command.as_str(), rest)) {
+        return fmt(paths);
+    }
+
+    let mut engine = Engine::default();
+    let mut disassemble = false;
+
+    // options come before the path; `-` alone is the path for stdin
+    let mut args = args.as_slice();
+    while let Some((flag, rest)) = args.split_first().filter(|(arg, _)| arg.starts_with("--")) {
+        if flag == "--disassemble" {
+            disassemble = true;
+        } else if let Some(name) = flag.strip_prefix("--engine=") {
+            engine = match name.parse() {
+                Ok(engine) => engine,
+                Err(e) => {
+                    eprintln!("{e}\n{USAGE}");
+                    return ExitCode::from(2);
+                }
+            };
+        } else {
+            eprintln!("{USAGE}");
+            return ExitCode::from(2);
+        }
+        args = rest;
+    }
+
+    match args {
+        [] if !disassemble => {
+            println!("Hello, this is the Monkey programming language!");
+
+            let color = use_color(std::io::stdout().is_terminal());
+            repl::start(std::io::stdin().lock(), std::io::stdout(), engine, color);
+            ExitCode::SUCCESS
+        }
+        [path] if path == "-" || !path.starts_with('-') => {
+            let name = if path == "-" { "<stdin>" } else { path.as_str() };
+            let source = match read_source(path) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("{name}: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            let color = use_color(std::io::stderr().is_terminal());
+            if disassemble {
+                runner::disassemble(name, &source, &mut std::io::stdout(), &mut std::io::stderr(), color)
+            } else {
+                runner::run(name, &source, engine, &mut std::io::stderr(), color)
+            }
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Rewrites each file in canonical form, or with `--check` only reports the
+/// ones that aren't. `-` formats stdin to stdout.
+fn fmt(args: &[String]) -> ExitCode {
+    let (check, paths) = match args.split_first() {
+        Some((flag, rest)) if flag == "--check" => (true, rest),
+        _ => (false, args),
+    };
+    if paths.is_empty() || paths.iter().any(|path| path.starts_with('-') && path != "-") {
+        eprintln!("{USAGE}");
+        return ExitCode::from(2);
+    }
+
+    let color = use_color(std::io::stderr().is_terminal());
+    let mut code = ExitCode::SUCCESS;
+    for path in paths {
+        let name = if path == "-" { "<stdin>" } else { path.as_str() };
+        let source = match read_source(path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{name}: {e}");
+                code = ExitCode::FAILURE;
+                continue;
+            }
+        };
+
+        let Some(formatted) = runner::format(name, &source, &mut std::io::stderr(), color) else {
+            code = ExitCode::FAILURE;
+            continue;
+        };
+
+        if check {
+            if formatted != source {
+                eprintln!("{name}: not formatted");
+                code = ExitCode::FAILURE;
+            }
+        } else if path == "-" {
+            print!("{formatted}");
+        } else if formatted != source {
+            if let Err(e) = std::fs::write(path, formatted) {
+                eprintln!("{name}: {e}");
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    code
+}
+
+fn read_source(path: &str) -> std::io::Result<String> {
+    if path == "-" {
+        let mut source = String::new();
+        std::io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+// https://no-color.org
+fn use_color(is_terminal: bool) -> bool {
+    is_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
 }
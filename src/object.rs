@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::ast::{BlockStatement, ExpressionNode, Identifier, Node};
+use crate::code::{Instructions, SourceMap};
+use crate::environment::Env;
+use crate::token::Span;
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Null,
+    ReturnValue(Box<Object>),
+    Error(RuntimeError),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+    Array(Rc<Vec<Object>>),
+    Hash(Rc<BTreeMap<HashKey, HashPair>>),
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure(Rc<Closure>),
+    Quote(Rc<ExpressionNode>),
+    Macro(Rc<Macro>),
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Error(_) => "ERROR",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) => "BUILTIN",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            // the same to scripts as an evaluated function
+            Object::Closure(_) => "FUNCTION",
+            Object::Quote(_) => "QUOTE",
+            Object::Macro(_) => "MACRO",
+        }
+    }
+
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::Integer(value) => value.to_string(),
+            Object::Boolean(value) => value.to_string(),
+            Object::String(value) => value.clone(),
+            Object::Null => "null".to_string(),
+            Object::ReturnValue(value) => value.inspect(),
+            Object::Error(err) => format!("ERROR: {}", err.message),
+            Object::Function(func) => func.inspect(),
+            Object::Builtin(builtin) => format!("builtin function {}", builtin.name),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.inspect()).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.values()
+                    .map(|pair| format!("{}: {}", pair.key.inspect(), pair.value.inspect()))
+                    .collect();
+                format!("{{{}}}", pairs.join(", "))
+            }
+            Object::CompiledFunction(func) => format!("CompiledFunction[{:p}]", Rc::as_ptr(func)),
+            Object::Closure(closure) => closure.func.source.clone(),
+            Object::Quote(node) => format!("QUOTE({})", node.print()),
+            Object::Macro(mac) => {
+                let params: Vec<String> = mac.parameters.iter().map(|p| p.print()).collect();
+                format!("macro({}) {}", params.join(", "), mac.body.print())
+            }
+        }
+    }
+
+    /// The key this object is stored under in a hash, or `None` for objects
+    /// that can't be keys.
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error(_))
+    }
+}
+
+// ordered so that hashes always inspect the same way
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct HashPair {
+    pub key: Object,
+    pub value: Object,
+}
+
+/// A function implemented in Rust. It gets the evaluated arguments and either
+/// returns a value or an error that is reported at the call site.
+pub type BuiltinFunction = Rc<dyn Fn(&[Object]) -> Result<Object, RuntimeError>>;
+
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: Rc<str>,
+    pub func: BuiltinFunction,
+}
+
+impl Builtin {
+    pub fn new<F>(name: &str, func: F) -> Self
+    where
+        F: Fn(&[Object]) -> Result<Object, RuntimeError> + 'static,
+    {
+        Builtin { name: Rc::from(name), func: Rc::new(func) }
+    }
+}
+
+impl fmt::Debug for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Builtin")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl RuntimeError {
+    pub fn new(span: Span, message: String) -> Self {
+        RuntimeError { message, span }
+    }
+}
+
+pub struct Function {
+    pub parameters: Rc<Vec<Identifier>>,
+    pub body: Rc<BlockStatement>,
+    pub env: Env,
+}
+
+impl Function {
+    pub fn inspect(&self) -> String {
+        let params: Vec<String> = self.parameters.iter().map(|p| p.print()).collect();
+        format!("fn({}) {}", params.join(", "), self.body.print())
+    }
+}
+
+/// A macro defined by a top-level `let`. It is called with its arguments
+/// quoted while the program is being expanded, and must return a quote.
+#[derive(Debug)]
+pub struct Macro {
+    pub parameters: Vec<Identifier>,
+    pub body: BlockStatement,
+}
+
+/// A function as the compiler emits it into the constant pool.
+#[derive(Debug, Default, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub source_map: SourceMap,
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    // the function literal printed back, which is how it inspects
+    pub source: String,
+}
+
+/// A compiled function together with the variables it captured from the
+/// functions enclosing it, as the VM calls it.
+#[derive(Debug)]
+pub struct Closure {
+    pub func: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+// the captured environment is left out: it can be arbitrarily large and
+// usually contains this very function
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("parameters", &self.parameters)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
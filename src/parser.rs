@@ -1,12 +1,95 @@
-use crate::lexer::Lexer;
-use crate::token::{Token, TokenKind};
-use crate::ast::{Program, StatementNode, Node, LetStatement, Identifier, ReturnStatement};
+use std::collections::HashMap;
+use std::fmt;
+use crate::lexer::{LexError, Lexer};
+use crate::token::{Span, Token, TokenKind};
+use crate::ast::{Program, StatementNode, ExpressionNode, LetStatement, Identifier, ReturnStatement, ExpressionStatement, IntegerLiteral, PrefixExpression, InfixExpression, BooleanLiteral, IfExpression, BlockStatement, FunctionLiteral, CallExpression, StringLiteral, ArrayLiteral, IndexExpression, HashLiteral, MacroLiteral};
+
+type PrefixParseFn = fn(&mut Parser) -> Option<ExpressionNode>;
+type InfixParseFn = fn(&mut Parser, ExpressionNode) -> Option<ExpressionNode>;
+
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub(crate) enum Precedence {
+    Lowest,
+    Equals,      // ==
+    LessGreater, // > or <
+    Sum,         // +
+    Product,     // *
+    Prefix,      // -X or !X
+    Call,        // myFunction(X)
+    Index,       // array[index]
+}
+
+pub(crate) fn precedence_map(kind: &TokenKind) -> Precedence {
+    match kind {
+        TokenKind::Eq | TokenKind::NotEq => Precedence::Equals,
+        TokenKind::Lt | TokenKind::Gt => Precedence::LessGreater,
+        TokenKind::Plus | TokenKind::Minus => Precedence::Sum,
+        TokenKind::Asterisk | TokenKind::Slash => Precedence::Product,
+        TokenKind::LParen => Precedence::Call,
+        TokenKind::LBracket => Precedence::Index,
+        _ => Precedence::Lowest,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedToken { expected: TokenKind, found: TokenKind, span: Span },
+    NoPrefixParseFn { kind: TokenKind, span: Span },
+    InvalidIntegerLiteral { literal: String, span: Span },
+    UnterminatedBlock { span: Span },
+    Lex(LexError),
+}
+
+impl ParseError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0001",
+            ParseError::NoPrefixParseFn { .. } => "E0002",
+            ParseError::InvalidIntegerLiteral { .. } => "E0003",
+            ParseError::UnterminatedBlock { .. } => "E0004",
+            ParseError::Lex(LexError::UnterminatedString { .. }) => "E0005",
+            ParseError::Lex(LexError::InvalidEscape { .. }) => "E0006",
+            ParseError::Lex(LexError::UnterminatedComment { .. }) => "E0007",
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::NoPrefixParseFn { span, .. }
+            | ParseError::InvalidIntegerLiteral { span, .. }
+            | ParseError::UnterminatedBlock { span } => *span,
+            ParseError::Lex(err) => err.span(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { expected, found, .. } => {
+                write!(f, "expected {}, found {}", expected.describe(), found.describe())
+            }
+            ParseError::NoPrefixParseFn { kind, .. } => write!(f, "expected an expression, found {}", kind.describe()),
+            ParseError::InvalidIntegerLiteral { literal, .. } => write!(f, "integer literal `{}` is out of range", literal),
+            ParseError::UnterminatedBlock { .. } => write!(f, "unclosed block"),
+            ParseError::Lex(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
 
 pub struct Parser {
     lexer: Lexer,
     cur_token: Token,
     peek_token: Token,
-    errors: Vec<String>,
+    errors: Vec<ParseError>,
+    prefix_parse_fns: HashMap<TokenKind, PrefixParseFn>,
+    infix_parse_fns: HashMap<TokenKind, InfixParseFn>,
+    // hash literals whose `{` has been consumed but not their `}`; a parse
+    // error inside one leaves them open for `synchronize` to skip
+    open_hashes: usize,
 }
 
 impl Parser {
@@ -16,8 +99,38 @@ impl Parser {
             cur_token: Default::default(),
             peek_token: Default::default(),
             errors: vec![],
+            prefix_parse_fns: HashMap::new(),
+            infix_parse_fns: HashMap::new(),
+            open_hashes: 0,
         };
 
+        parser.register_prefix(TokenKind::Ident, Self::parse_identifier);
+        parser.register_prefix(TokenKind::Int, Self::parse_integer_literal);
+        parser.register_prefix(TokenKind::String, Self::parse_string_literal);
+        parser.register_prefix(TokenKind::Bang, Self::parse_prefix_expression);
+        parser.register_prefix(TokenKind::Minus, Self::parse_prefix_expression);
+        parser.register_prefix(TokenKind::True, Self::parse_boolean);
+        parser.register_prefix(TokenKind::False, Self::parse_boolean);
+        parser.register_prefix(TokenKind::LParen, Self::parse_grouped_expression);
+        parser.register_prefix(TokenKind::If, Self::parse_if_expression);
+        parser.register_prefix(TokenKind::Function, Self::parse_function_literal);
+        parser.register_prefix(TokenKind::Macro, Self::parse_macro_literal);
+        parser.register_prefix(TokenKind::LBracket, Self::parse_array_literal);
+        // blocks are only parsed where `if` and `fn` expect one, so a `{`
+        // reaching the expression parser is always a hash
+        parser.register_prefix(TokenKind::LBrace, Self::parse_hash_literal);
+
+        parser.register_infix(TokenKind::Plus, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Minus, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Slash, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Asterisk, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Eq, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::NotEq, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Lt, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::Gt, Self::parse_infix_expression);
+        parser.register_infix(TokenKind::LParen, Self::parse_call_expression);
+        parser.register_infix(TokenKind::LBracket, Self::parse_index_expression);
+
         parser.next_token();
         parser.next_token();
 
@@ -27,72 +140,462 @@ impl Parser {
     fn next_token(&mut self) {
         self.cur_token = self.peek_token.clone();
         self.peek_token = self.lexer.next_token();
+        self.errors.extend(self.lexer.take_errors().into_iter().map(ParseError::Lex));
     }
 
-    pub fn parse_program(&mut self) -> Option<Program> {
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
         let mut program = Program {
             statements: vec![]
         };
 
         while !self.cur_token_is(TokenKind::EOF) {
-            if let Some(stmt) = self.parse_statement() {
-                program.statements.push(stmt);
+            match self.parse_statement() {
+                Some(stmt) => program.statements.push(stmt),
+                None => self.synchronize(),
             }
             self.next_token();
         }
 
-        Some(program)
+        if self.errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
     fn parse_statement(&mut self) -> Option<StatementNode> {
         match self.cur_token.kind {
             TokenKind::Let => self.parse_let_statement(),
             TokenKind::Return => self.parse_return_statement(),
-            _ => None
+            _ => self.parse_expression_statement(),
         }
     }
 
     fn parse_let_statement(&mut self) -> Option<StatementNode> {
-        let mut stmt: LetStatement = LetStatement {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+
+        let name = Identifier {
             token: self.cur_token.clone(),
-            name: Default::default(),
-            value: None,
+            value: self.cur_token.literal.clone(),
         };
 
-        return if !self.expect_peek(TokenKind::Ident) {
-            None
-        } else {
-            stmt.name = Identifier {
-                token: self.cur_token.clone(),
-                value: self.cur_token.literal.clone(),
+        if !self.expect_peek(TokenKind::Assign) {
+            return None;
+        }
+
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Let(LetStatement { token, name, value: Some(value) }))
+    }
+
+    fn parse_return_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+
+        self.next_token();
+        let return_value = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Return(ReturnStatement { token, return_value: Some(return_value) }))
+    }
+
+    fn parse_expression_statement(&mut self) -> Option<StatementNode> {
+        let token = self.cur_token.clone();
+        let expression = self.parse_expression(Precedence::Lowest)?;
+
+        if self.peek_token_is(TokenKind::Semicolon) {
+            self.next_token();
+        }
+
+        Some(StatementNode::Expression(ExpressionStatement { token, expression: Some(expression) }))
+    }
+
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<ExpressionNode> {
+        let prefix = match self.prefix_parse_fns.get(&self.cur_token.kind) {
+            Some(prefix) => *prefix,
+            None => {
+                self.no_prefix_parse_fn_error(self.cur_token.kind.clone());
+                return None;
+            }
+        };
+
+        let mut left_exp = prefix(self)?;
+
+        while !self.peek_token_is(TokenKind::Semicolon) && precedence < self.peek_precedence() {
+            let infix = match self.infix_parse_fns.get(&self.peek_token.kind) {
+                Some(infix) => *infix,
+                None => return Some(left_exp),
             };
 
-            if !self.expect_peek(TokenKind::Assign) {
+            self.next_token();
+            left_exp = infix(self, left_exp)?;
+        }
+
+        Some(left_exp)
+    }
+
+    fn parse_identifier(&mut self) -> Option<ExpressionNode> {
+        Some(ExpressionNode::IdentifierNode(Identifier {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        }))
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<ExpressionNode> {
+        match self.cur_token.literal.parse::<i64>() {
+            Ok(value) => Some(ExpressionNode::Integer(IntegerLiteral {
+                token: self.cur_token.clone(),
+                value,
+            })),
+            Err(_) => {
+                self.errors.push(ParseError::InvalidIntegerLiteral {
+                    literal: self.cur_token.literal.clone(),
+                    span: self.cur_token.span,
+                });
                 None
-            } else {
-                self.next_token();
-                while !self.cur_token_is(TokenKind::Semicolon) {
-                    self.next_token();
-                }
+            }
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Option<ExpressionNode> {
+        Some(ExpressionNode::String(StringLiteral {
+            token: self.cur_token.clone(),
+            value: self.cur_token.literal.clone(),
+        }))
+    }
+
+    fn parse_prefix_expression(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+
+        self.next_token();
+        let right = self.parse_expression(Precedence::Prefix)?;
+
+        Some(ExpressionNode::Prefix(PrefixExpression {
+            token,
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_infix_expression(&mut self, left: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let operator = self.cur_token.literal.clone();
+        let precedence = self.cur_precedence();
+
+        self.next_token();
+        let right = self.parse_expression(precedence)?;
+
+        Some(ExpressionNode::Infix(InfixExpression {
+            token,
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        }))
+    }
+
+    fn parse_boolean(&mut self) -> Option<ExpressionNode> {
+        Some(ExpressionNode::Boolean(BooleanLiteral {
+            token: self.cur_token.clone(),
+            value: self.cur_token_is(TokenKind::True),
+        }))
+    }
+
+    fn parse_grouped_expression(&mut self) -> Option<ExpressionNode> {
+        self.next_token();
+        let exp = self.parse_expression(Precedence::Lowest);
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
 
-                Some(StatementNode::Let(stmt))
+        exp
+    }
+
+    fn parse_if_expression(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        if !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        let consequence = self.parse_block_statement()?;
+        let mut alternative = None;
+
+        if self.peek_token_is(TokenKind::Else) {
+            self.next_token();
+
+            if !self.expect_peek(TokenKind::LBrace) {
+                return None;
             }
+
+            alternative = Some(self.parse_block_statement()?);
+        }
+
+        Some(ExpressionNode::If(IfExpression {
+            token,
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+        }))
+    }
+
+    fn parse_block_statement(&mut self) -> Option<BlockStatement> {
+        let mut block = BlockStatement {
+            token: self.cur_token.clone(),
+            statements: vec![],
         };
+        // the hashes around the block are still open once it's parsed;
+        // recovering inside it only skips the ones opened in it
+        let open_hashes = std::mem::take(&mut self.open_hashes);
+
+        self.next_token();
+
+        while !self.cur_token_is(TokenKind::RBrace) && !self.cur_token_is(TokenKind::EOF) {
+            match self.parse_statement() {
+                Some(stmt) => block.statements.push(stmt),
+                None => self.synchronize(),
+            }
+            self.next_token();
+        }
+
+        self.open_hashes = open_hashes;
+        if self.cur_token_is(TokenKind::EOF) {
+            self.errors.push(ParseError::UnterminatedBlock { span: block.token.span });
+            return None;
+        }
+
+        Some(block)
     }
 
-    fn parse_return_statement(&mut self) -> Option<StatementNode> {
-        let stmt = StatementNode::Return(ReturnStatement {
+    fn parse_function_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let (parameters, body) = self.parse_parameters_and_body()?;
+
+        Some(ExpressionNode::Function(FunctionLiteral {
+            token,
+            parameters,
+            body,
+        }))
+    }
+
+    fn parse_macro_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let (parameters, body) = self.parse_parameters_and_body()?;
+
+        Some(ExpressionNode::Macro(MacroLiteral {
+            token,
+            parameters,
+            body,
+        }))
+    }
+
+    /// The `(a, b) { ... }` following `fn` or `macro`.
+    fn parse_parameters_and_body(&mut self) -> Option<(Vec<Identifier>, BlockStatement)> {
+        if !self.expect_peek(TokenKind::LParen) {
+            return None;
+        }
+
+        let parameters = self.parse_function_parameters()?;
+
+        if !self.expect_peek(TokenKind::LBrace) {
+            return None;
+        }
+
+        let body = self.parse_block_statement()?;
+        Some((parameters, body))
+    }
+
+    fn parse_function_parameters(&mut self) -> Option<Vec<Identifier>> {
+        let mut identifiers = vec![];
+
+        if self.peek_token_is(TokenKind::RParen) {
+            self.next_token();
+            return Some(identifiers);
+        }
+
+        if !self.expect_peek(TokenKind::Ident) {
+            return None;
+        }
+
+        identifiers.push(Identifier {
             token: self.cur_token.clone(),
-            return_value: Default::default(),
+            value: self.cur_token.literal.clone(),
         });
 
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+
+            if !self.expect_peek(TokenKind::Ident) {
+                return None;
+            }
+
+            identifiers.push(Identifier {
+                token: self.cur_token.clone(),
+                value: self.cur_token.literal.clone(),
+            });
+        }
+
+        if !self.expect_peek(TokenKind::RParen) {
+            return None;
+        }
+
+        Some(identifiers)
+    }
+
+    fn parse_call_expression(&mut self, function: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let arguments = self.parse_expression_list(TokenKind::RParen)?;
+
+        Some(ExpressionNode::Call(CallExpression {
+            token,
+            function: Box::new(function),
+            arguments,
+        }))
+    }
+
+    fn parse_array_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let elements = self.parse_expression_list(TokenKind::RBracket)?;
+
+        Some(ExpressionNode::Array(ArrayLiteral { token, elements }))
+    }
+
+    fn parse_index_expression(&mut self, left: ExpressionNode) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+
+        if !self.expect_peek(TokenKind::RBracket) {
+            return None;
+        }
+
+        Some(ExpressionNode::Index(IndexExpression {
+            token,
+            left: Box::new(left),
+            index: Box::new(index),
+        }))
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<ExpressionNode> {
+        let token = self.cur_token.clone();
+        let mut pairs = vec![];
+        self.open_hashes += 1;
+
+        while !self.peek_token_is(TokenKind::RBrace) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+
+            if !self.expect_peek(TokenKind::Colon) {
+                return None;
+            }
+
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenKind::RBrace) && !self.expect_peek(TokenKind::Comma) {
+                return None;
+            }
+        }
+
+        if !self.expect_peek(TokenKind::RBrace) {
+            return None;
+        }
+
+        self.open_hashes -= 1;
+        Some(ExpressionNode::Hash(HashLiteral { token, pairs }))
+    }
+
+    fn parse_expression_list(&mut self, end: TokenKind) -> Option<Vec<ExpressionNode>> {
+        let mut list = vec![];
+
+        if self.peek_token_is(end.clone()) {
+            self.next_token();
+            return Some(list);
+        }
+
         self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        if !self.expect_peek(end) {
+            return None;
+        }
+
+        Some(list)
+    }
+
+    /// Skips the rest of a statement that failed to parse, so that one mistake
+    /// is reported once instead of setting off errors for every token after
+    /// it. Stops on the statement's `;`, or just before a `}` closing the
+    /// enclosing block or a keyword that starts a new statement. Braces opened
+    /// while skipping, or by a hash literal the error happened in, are skipped
+    /// as a whole.
+    fn synchronize(&mut self) {
+        let mut depth = std::mem::take(&mut self.open_hashes);
+
+        while !self.cur_token_is(TokenKind::EOF) {
+            if depth == 0 {
+                if self.cur_token_is(TokenKind::Semicolon) {
+                    return;
+                }
+
+                match self.peek_token.kind {
+                    TokenKind::RBrace
+                    | TokenKind::Let
+                    | TokenKind::Return
+                    | TokenKind::Function
+                    | TokenKind::Macro
+                    | TokenKind::If => return,
+                    _ => {}
+                }
+            }
 
-        while !self.cur_token_is(TokenKind::Semicolon) {
             self.next_token();
+
+            match self.cur_token.kind {
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth > 0 => depth -= 1,
+                _ => {}
+            }
         }
+    }
+
+    fn register_prefix(&mut self, kind: TokenKind, func: PrefixParseFn) {
+        self.prefix_parse_fns.insert(kind, func);
+    }
 
-        Some(stmt)
+    fn register_infix(&mut self, kind: TokenKind, func: InfixParseFn) {
+        self.infix_parse_fns.insert(kind, func);
     }
 
     fn expect_peek(&mut self, kind: TokenKind) -> bool {
@@ -112,19 +615,32 @@ impl Parser {
         self.cur_token.kind == kind
     }
 
-    fn errors(&self) -> &Vec<String> {
-        &self.errors
+    fn peek_precedence(&self) -> Precedence {
+        precedence_map(&self.peek_token.kind)
+    }
+
+    fn cur_precedence(&self) -> Precedence {
+        precedence_map(&self.cur_token.kind)
     }
 
     fn peek_error(&mut self, kind: TokenKind) {
-        let msg = format!("expected next token to be {:?}, got {:?} instead", kind, self.peek_token.kind);
-        self.errors.push(msg);
+        self.errors.push(ParseError::UnexpectedToken {
+            expected: kind,
+            found: self.peek_token.kind.clone(),
+            span: self.peek_token.span,
+        });
+    }
+
+    fn no_prefix_parse_fn_error(&mut self, kind: TokenKind) {
+        self.errors.push(ParseError::NoPrefixParseFn { kind, span: self.cur_token.span });
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::Node;
+    use crate::token::Position;
 
     #[test]
     fn test_let_statements() {
@@ -137,21 +653,15 @@ mod tests {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
 
-        let program = parser.parse_program();
-        check_parser_error(parser);
+        let p = check_parser_error(parser.parse_program());
 
-        match program {
-            Some(p) => {
-                assert_eq!(p.statements.len(), 3, "program.statements does not contain 3 statements. got {}", p.statements.len());
+        assert_eq!(p.statements.len(), 3, "program.statements does not contain 3 statements. got {}", p.statements.len());
 
-                let tests = vec!["x", "y", "foobar"];
+        let tests = ["x", "y", "foobar"];
 
-                for (i, tt) in tests.iter().enumerate() {
-                    let stmt = &p.statements[i];
-                    test_let_statement(stmt, tt)
-                }
-            }
-            None => panic!("parse program should not be None")
+        for (i, tt) in tests.iter().enumerate() {
+            let stmt = &p.statements[i];
+            test_let_statement(stmt, tt)
         }
     }
 
@@ -166,23 +676,636 @@ mod tests {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
 
-        let program = parser.parse_program();
-        check_parser_error(parser);
+        let p = check_parser_error(parser.parse_program());
 
-        match program {
-            Some(p) => {
-                assert_eq!(p.statements.len(), 3, "program.statements does not contain 3 statements. got {}", p.statements.len());
-
-                for stmt in p.statements {
-                    match stmt {
-                        StatementNode::Return(ret) => {
-                            assert_eq!(ret.token_literal(), "return", "return statement token literal not 'return', got {}", ret.token_literal());
-                        },
-                        _ => panic!("stmt is not ReturnStatement. got {:?}", stmt)
-                    }
+        assert_eq!(p.statements.len(), 3, "program.statements does not contain 3 statements. got {}", p.statements.len());
+
+        for stmt in p.statements {
+            match stmt {
+                StatementNode::Return(ret) => {
+                    assert_eq!(ret.token_literal(), "return", "return statement token literal not 'return', got {}", ret.token_literal());
+                },
+                _ => panic!("stmt is not ReturnStatement. got {:?}", stmt)
+            }
+        }
+    }
+
+    #[test]
+    fn test_let_statement_values() {
+        let tests = [
+            ("let x = 5;", "x", "5"),
+            ("let y = -a * b;", "y", "((-a) * b)"),
+            ("let foobar = 5 + 5 * 10 == 55;", "foobar", "((5 + (5 * 10)) == 55)"),
+        ];
+
+        for (input, name, value) in tests {
+            let program = parse(input);
+            assert_eq!(program.statements.len(), 1, "program.statements does not contain 1 statement. got {}", program.statements.len());
+
+            let stmt = &program.statements[0];
+            test_let_statement(stmt, name);
+            match stmt {
+                StatementNode::Let(e) => match &e.value {
+                    Some(v) => assert_eq!(v.print(), value),
+                    None => panic!("LetStatement value should not be None"),
+                },
+                other => panic!("stmt is not LetStatement. got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_literal_expression() {
+        let program = parse("return 5;");
+
+        match return_value(&program) {
+            ExpressionNode::Integer(lit) => {
+                assert_eq!(lit.value, 5, "literal value not 5, got {}", lit.value);
+                assert_eq!(lit.token_literal(), "5", "literal token_literal not 5, got {}", lit.token_literal());
+            }
+            other => panic!("exp is not IntegerLiteral. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_parsing_prefix_expressions() {
+        let tests = [
+            ("return !5;", "!", 5),
+            ("return -15;", "-", 15),
+        ];
+
+        for (input, operator, value) in tests {
+            let program = parse(input);
+
+            match return_value(&program) {
+                ExpressionNode::Prefix(exp) => {
+                    assert_eq!(exp.operator, operator, "exp.operator is not {}, got {}", operator, exp.operator);
+                    test_integer_literal(&exp.right, value);
+                }
+                other => panic!("exp is not PrefixExpression. got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_parsing_infix_expressions() {
+        let tests = [
+            ("return 5 + 5;", 5, "+", 5),
+            ("return 5 - 5;", 5, "-", 5),
+            ("return 5 * 5;", 5, "*", 5),
+            ("return 5 / 5;", 5, "/", 5),
+            ("return 5 > 5;", 5, ">", 5),
+            ("return 5 < 5;", 5, "<", 5),
+            ("return 5 == 5;", 5, "==", 5),
+            ("return 5 != 5;", 5, "!=", 5),
+        ];
+
+        for (input, left, operator, right) in tests {
+            let program = parse(input);
+
+            match return_value(&program) {
+                ExpressionNode::Infix(exp) => {
+                    test_integer_literal(&exp.left, left);
+                    assert_eq!(exp.operator, operator, "exp.operator is not {}, got {}", operator, exp.operator);
+                    test_integer_literal(&exp.right, right);
                 }
+                other => panic!("exp is not InfixExpression. got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_operator_precedence_parsing() {
+        let tests = [
+            ("return -a * b;", "((-a) * b)"),
+            ("return !-a;", "(!(-a))"),
+            ("return a + b + c;", "((a + b) + c)"),
+            ("return a + b - c;", "((a + b) - c)"),
+            ("return a * b * c;", "((a * b) * c)"),
+            ("return a * b / c;", "((a * b) / c)"),
+            ("return a + b / c;", "(a + (b / c))"),
+            ("return a + b * c + d / e - f;", "(((a + (b * c)) + (d / e)) - f)"),
+            ("return 5 > 4 == 3 < 4;", "((5 > 4) == (3 < 4))"),
+            ("return 5 < 4 != 3 > 4;", "((5 < 4) != (3 > 4))"),
+            ("return 3 + 4 * 5 == 3 * 1 + 4 * 5;", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            let actual = return_value(&program).print();
+            assert_eq!(actual, expected, "expected={}, got={}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_identifier_expression() {
+        let program = parse("foobar;");
+
+        match expression(&program) {
+            ExpressionNode::IdentifierNode(ident) => {
+                assert_eq!(ident.value, "foobar", "ident.value not foobar, got {}", ident.value);
+                assert_eq!(ident.token_literal(), "foobar", "ident.token_literal not foobar, got {}", ident.token_literal());
+            }
+            other => panic!("exp is not Identifier. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_expression_statements() {
+        let tests = [
+            ("x + 5;", "(x + 5)"),
+            ("x + 5", "(x + 5)"),
+            ("-a * b", "((-a) * b)"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            let actual = expression(&program).print();
+            assert_eq!(actual, expected, "expected={}, got={}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_string_literal_expression() {
+        let program = parse(r#""hello\tworld\n";"#);
+
+        match expression(&program) {
+            ExpressionNode::String(lit) => {
+                assert_eq!(lit.value, "hello\tworld\n", "literal value wrong, got {:?}", lit.value);
+                assert_eq!(lit.print(), r#""hello\tworld\n""#);
+            }
+            other => panic!("exp is not StringLiteral. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_string_errors() {
+        let tests = [
+            (r#"let s = "abc"#, "1:9: unterminated string literal"),
+            (r#"let s = "a\qb";"#, r"1:11: invalid escape sequence `\q` in string"),
+            (r#"let s = "\u{110000}";"#, r"1:10: invalid escape sequence `\u{110000}` in string"),
+            (r#"let s = "\u{zz}";"#, r"1:10: invalid escape sequence `\u{` in string"),
+        ];
+
+        for (input, expected) in tests {
+            let errors: Vec<String> = parse_errors(input).iter()
+                .map(|e| format!("{}: {}", e.span().start, e))
+                .collect();
+            assert_eq!(errors, [expected], "wrong errors for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_boolean_expression() {
+        let tests = [("true;", true), ("false;", false)];
+
+        for (input, value) in tests {
+            let program = parse(input);
+
+            match expression(&program) {
+                ExpressionNode::Boolean(b) => {
+                    assert_eq!(b.value, value, "boolean value not {}, got {}", value, b.value);
+                }
+                other => panic!("exp is not BooleanLiteral. got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_boolean_and_grouped_precedence() {
+        let tests = [
+            ("true", "true"),
+            ("3 > 5 == false", "((3 > 5) == false)"),
+            ("3 < 5 == true", "((3 < 5) == true)"),
+            ("1 + (2 + 3) + 4", "((1 + (2 + 3)) + 4)"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("2 / (5 + 5)", "(2 / (5 + 5))"),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            let actual = expression(&program).print();
+            assert_eq!(actual, expected, "expected={}, got={}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_if_expression() {
+        let program = parse("if (x < y) { x }");
+
+        match expression(&program) {
+            ExpressionNode::If(exp) => {
+                assert_eq!(exp.condition.print(), "(x < y)");
+                assert_eq!(exp.consequence.statements.len(), 1, "consequence is not 1 statement. got {}", exp.consequence.statements.len());
+                assert_eq!(exp.consequence.statements[0].print(), "x");
+                assert!(exp.alternative.is_none(), "exp.alternative was not None. got {:?}", exp.alternative);
+            }
+            other => panic!("exp is not IfExpression. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_if_else_expression() {
+        let program = parse("if (x < y) { x } else { y }");
+
+        match expression(&program) {
+            ExpressionNode::If(exp) => {
+                assert_eq!(exp.condition.print(), "(x < y)");
+                assert_eq!(exp.consequence.print(), "{ x; }");
+                match &exp.alternative {
+                    Some(alt) => assert_eq!(alt.print(), "{ y; }"),
+                    None => panic!("exp.alternative should not be None"),
+                }
+            }
+            other => panic!("exp is not IfExpression. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_if_expression_print_round_trips() {
+        let inputs = [
+            "if (5 < 10) { return true; } else { return false; }",
+            "if (x) { let y = x * 2; y } else { !x }",
+            "if (a) { if (b) { c } }",
+        ];
+
+        for input in inputs {
+            let printed = parse(input).print();
+            let reprinted = parse(&printed).print();
+            assert_eq!(printed, reprinted, "print did not round-trip for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_function_literal_parsing() {
+        let program = parse("fn(x, y) { x + y; }");
+
+        match expression(&program) {
+            ExpressionNode::Function(func) => {
+                let params: Vec<&str> = func.parameters.iter().map(|p| p.value.as_str()).collect();
+                assert_eq!(params, ["x", "y"]);
+                assert_eq!(func.body.statements.len(), 1, "function.body.statements has not 1 statement. got {}", func.body.statements.len());
+                assert_eq!(func.body.statements[0].print(), "(x + y)");
+            }
+            other => panic!("exp is not FunctionLiteral. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_macro_literal_parsing() {
+        let program = parse("macro(x, y) { x + y; }");
+
+        match expression(&program) {
+            ExpressionNode::Macro(mac) => {
+                let params: Vec<&str> = mac.parameters.iter().map(|p| p.value.as_str()).collect();
+                assert_eq!(params, ["x", "y"]);
+                assert_eq!(mac.body.print(), "{ (x + y); }");
+                assert_eq!(mac.print(), "macro(x, y) { (x + y); }");
+            }
+            other => panic!("exp is not MacroLiteral. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_function_parameter_parsing() {
+        let tests: [(&str, &[&str]); 3] = [
+            ("fn() {};", &[]),
+            ("fn(x) {};", &["x"]),
+            ("fn(x, y, z) {};", &["x", "y", "z"]),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+
+            match expression(&program) {
+                ExpressionNode::Function(func) => {
+                    let params: Vec<&str> = func.parameters.iter().map(|p| p.value.as_str()).collect();
+                    assert_eq!(params, expected);
+                }
+                other => panic!("exp is not FunctionLiteral. got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_call_expression_parsing() {
+        let program = parse("add(1, 2 * 3, 4 + 5);");
+
+        match expression(&program) {
+            ExpressionNode::Call(call) => {
+                assert_eq!(call.function.print(), "add");
+                let args: Vec<String> = call.arguments.iter().map(|a| a.print()).collect();
+                assert_eq!(args, ["1", "(2 * 3)", "(4 + 5)"]);
             }
-            None => panic!("parse program should not be None")
+            other => panic!("exp is not CallExpression. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_call_precedence_parsing() {
+        let tests = [
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            ("add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))", "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))"),
+            ("add(a + b + c * d / f + g)", "add((((a + b) + ((c * d) / f)) + g))"),
+            ("fn(x) { x }(5)", "fn(x) { x; }(5)"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            let actual = expression(&program).print();
+            assert_eq!(actual, expected, "expected={}, got={}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_array_literal_parsing() {
+        let program = parse("[1, 2 * 2, 3 + 3]");
+
+        match expression(&program) {
+            ExpressionNode::Array(array) => {
+                let elements: Vec<String> = array.elements.iter().map(|e| e.print()).collect();
+                assert_eq!(elements, ["1", "(2 * 2)", "(3 + 3)"]);
+            }
+            other => panic!("exp is not ArrayLiteral. got {:?}", other)
+        }
+
+        match expression(&parse("[]")) {
+            ExpressionNode::Array(array) => assert!(array.elements.is_empty(), "array.elements not empty. got {:?}", array.elements),
+            other => panic!("exp is not ArrayLiteral. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_index_expression_parsing() {
+        let program = parse("myArray[1 + 1]");
+
+        match expression(&program) {
+            ExpressionNode::Index(exp) => {
+                assert_eq!(exp.left.print(), "myArray");
+                assert_eq!(exp.index.print(), "(1 + 1)");
+            }
+            other => panic!("exp is not IndexExpression. got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_index_precedence_parsing() {
+        let tests = [
+            ("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d)"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))"),
+            ("f(x)[0]", "(f(x)[0])"),
+            ("-a[0]", "(-(a[0]))"),
+        ];
+
+        for (input, expected) in tests {
+            let program = parse(input);
+            let actual = expression(&program).print();
+            assert_eq!(actual, expected, "expected={}, got={}", expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_hash_literal_parsing() {
+        let tests = [
+            (r#"{"one": 1, "two": 2, "three": 3}"#, vec![(r#""one""#, "1"), (r#""two""#, "2"), (r#""three""#, "3")]),
+            (r#"{"one": 0 + 1, "two": 10 - 8}"#, vec![(r#""one""#, "(0 + 1)"), (r#""two""#, "(10 - 8)")]),
+            ("{1: true, false: [1], x: fn(y) { y }}", vec![("1", "true"), ("false", "[1]"), ("x", "fn(y) { y; }")]),
+            ("{}", vec![]),
+        ];
+
+        for (input, expected) in tests {
+            match expression(&parse(input)) {
+                ExpressionNode::Hash(hash) => {
+                    let pairs: Vec<(String, String)> = hash.pairs.iter()
+                        .map(|(key, value)| (key.print(), value.print()))
+                        .collect();
+                    let expected: Vec<(String, String)> = expected.iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect();
+                    assert_eq!(pairs, expected, "wrong pairs for {}", input);
+                }
+                other => panic!("exp is not HashLiteral. got {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_literal_in_expression_position() {
+        let tests = [
+            (r#"let h = {"a": 1}; h["a"]"#, r#"let h = {"a": 1};(h["a"])"#),
+            (r#"if (x) { {"a": 1} } else { {} }"#, r#"if (x) { {"a": 1}; } else { {}; }"#),
+            (r#"fn() { {"a": 1}["a"] }"#, r#"fn() { ({"a": 1}["a"]); }"#),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(parse(input).print(), expected, "wrong program for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_hash_literal_errors() {
+        let tests = [
+            (r#"{"a" 1}"#, "1:6: expected `:`, found integer"),
+            (r#"{"a": 1 "b": 2}"#, "1:9: expected `,`, found string"),
+            (r#"{"a": }"#, "1:7: expected an expression, found `}`"),
+            (r#"let h = {"a": {"b" 2}}; let y = 1;"#, "1:20: expected `:`, found integer"),
+            (r#"fn() { {1 2} }; let y = 1;"#, "1:11: expected `:`, found integer"),
+            (r#"{1: fn() { let = 1; }}; let y = 2;"#, "1:16: expected identifier, found `=`"),
+        ];
+
+        for (input, expected) in tests {
+            let errors: Vec<String> = parse_errors(input).iter()
+                .map(|e| format!("{}: {}", e.span().start, e))
+                .collect();
+            assert_eq!(errors, [expected], "wrong errors for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_lexer_test_input() {
+        let input = r#"
+        let five = 5;
+        let ten = 10;
+
+        let add = fn(x, y) {
+            x + y;
+        };
+
+        let result = add(five, ten);
+        !-/ *5;
+        5 < 10 > 5;
+
+        if (5 < 10) {
+            return true;
+        } else {
+            return false;
+        }
+
+        10 == 10;
+        10 != 9;
+        "#;
+
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+        let errors = parser.parse_program().expect_err("`!-/ *5;` should not parse");
+
+        // `!-/ *5;` is deliberately malformed: `/` has no prefix parse function
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages, ["expected an expression, found `/`"]);
+
+        let program = parse(&input.replace("!-/ *5;", ""));
+
+        let expected = [
+            "let five = 5;",
+            "let ten = 10;",
+            "let add = fn(x, y) { (x + y); };",
+            "let result = add(five, ten);",
+            "((5 < 10) > 5)",
+            "if ((5 < 10)) { return true; } else { return false; }",
+            "(10 == 10)",
+            "(10 != 9)",
+        ];
+        let actual: Vec<String> = program.statements.iter()
+            .map(|s| s.print())
+            .filter(|s| !s.is_empty())
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_mixed_statements() {
+        let program = parse("let x = 5; x + 5; return x; x");
+
+        assert_eq!(program.statements.len(), 4, "program.statements does not contain 4 statements. got {}", program.statements.len());
+        assert_eq!(program.print(), "let x = 5;(x + 5)return x;x");
+    }
+
+    #[test]
+    fn test_no_prefix_parse_fn_error() {
+        let errors = parse_errors("let x = ;");
+
+        assert_eq!(errors.len(), 1, "expected 1 error. got {:?}", errors);
+        assert_eq!(errors[0].to_string(), "expected an expression, found `;`");
+        assert_eq!(errors[0].span().start.to_string(), "1:9");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = [
+            ("let 5 = x;", ParseError::UnexpectedToken {
+                expected: TokenKind::Ident,
+                found: TokenKind::Int,
+                span: span((1, 5, 4), (1, 6, 5)),
+            }),
+            ("let x = 99999999999999999999;", ParseError::InvalidIntegerLiteral {
+                literal: "99999999999999999999".to_string(),
+                span: span((1, 9, 8), (1, 29, 28)),
+            }),
+            ("if (x) { x", ParseError::UnterminatedBlock {
+                span: span((1, 8, 7), (1, 9, 8)),
+            }),
+            ("fn(x) {\n  x + 1;", ParseError::UnterminatedBlock {
+                span: span((1, 7, 6), (1, 8, 7)),
+            }),
+            ("let x = 1; /* open /* nested */", ParseError::Lex(LexError::UnterminatedComment {
+                span: span((1, 12, 11), (1, 32, 31)),
+            })),
+        ];
+
+        for (input, expected) in tests {
+            let errors = parse_errors(input);
+            assert_eq!(errors, [expected], "wrong errors for {}", input);
+        }
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let tests: [(&str, &[&str]); 7] = [
+            ("let = 5; let y 10; let z = 1;", &[
+                "1:5: expected identifier, found `=`",
+                "1:16: expected `=`, found integer",
+            ]),
+            ("let x = 5 +; let y = ); x * y", &[
+                "1:12: expected an expression, found `;`",
+                "1:22: expected an expression, found `)`",
+            ]),
+            ("let add = fn(x, ) { x + y; }; add(1, 2);\nreturn * 2", &[
+                "1:17: expected identifier, found `)`",
+                "2:8: expected an expression, found `*`",
+            ]),
+            ("fn(x { x }; let y = 1", &[
+                "1:6: expected `)`, found `{`",
+            ]),
+            ("if (x) { let = 1; x } else { 2 + ; }\nlet ok = 1;", &[
+                "1:14: expected identifier, found `=`",
+                "1:34: expected an expression, found `;`",
+            ]),
+            ("let a = 1 let b = 2; let c = / 3", &[
+                "1:30: expected an expression, found `/`",
+            ]),
+            ("let f = fn(x) {\n  let = x;\n  return x\n", &[
+                "2:7: expected identifier, found `=`",
+                "1:15: unclosed block",
+            ]),
+        ];
+
+        for (input, expected) in tests {
+            let errors: Vec<String> = parse_errors(input).iter()
+                .map(|e| format!("{}: {}", e.span().start, e))
+                .collect();
+            assert_eq!(errors, expected, "wrong errors for {:?}", input);
+        }
+    }
+
+    fn span(start: (usize, usize, usize), end: (usize, usize, usize)) -> Span {
+        Span {
+            start: Position { line: start.0, column: start.1, offset: start.2 },
+            end: Position { line: end.0, column: end.1, offset: end.2 },
+        }
+    }
+
+    fn parse_errors(input: &str) -> Vec<ParseError> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        parser.parse_program().expect_err("input should not parse")
+    }
+
+    fn parse(input: &str) -> Program {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer);
+
+        check_parser_error(parser.parse_program())
+    }
+
+    fn return_value(program: &Program) -> &ExpressionNode {
+        assert_eq!(program.statements.len(), 1, "program.statements does not contain 1 statement. got {}", program.statements.len());
+
+        match &program.statements[0] {
+            StatementNode::Return(ret) => ret.return_value.as_ref().expect("return value should not be None"),
+            other => panic!("stmt is not ReturnStatement. got {:?}", other)
+        }
+    }
+
+    fn expression(program: &Program) -> &ExpressionNode {
+        assert_eq!(program.statements.len(), 1, "program.statements does not contain 1 statement. got {}", program.statements.len());
+
+        match &program.statements[0] {
+            StatementNode::Expression(stmt) => stmt.expression.as_ref().expect("expression should not be None"),
+            other => panic!("stmt is not ExpressionStatement. got {:?}", other)
+        }
+    }
+
+    fn test_integer_literal(exp: &ExpressionNode, value: i64) {
+        match exp {
+            ExpressionNode::Integer(lit) => {
+                assert_eq!(lit.value, value, "literal value not {}, got {}", value, lit.value);
+                assert_eq!(lit.token_literal(), value.to_string(), "literal token_literal not {}, got {}", value, lit.token_literal());
+            }
+            other => panic!("exp is not IntegerLiteral. got {:?}", other)
         }
     }
 
@@ -197,16 +1320,16 @@ mod tests {
         };
     }
 
-    fn check_parser_error(parser: Parser) {
-        let errors = parser.errors();
-        if errors.len() == 0 {
-            return;
-        }
+    fn check_parser_error(program: Result<Program, Vec<ParseError>>) -> Program {
+        match program {
+            Ok(program) => program,
+            Err(errors) => {
+                for err in &errors {
+                    eprintln!("parser error at {}: {}", err.span().start, err);
+                }
 
-        for msg in errors.clone().into_iter() {
-            eprintln!("parser error: {}", msg);
+                panic!("parser has {} errors", errors.len());
+            }
         }
-
-        panic!("parser has {} errors", errors.len());
     }
 }
\ No newline at end of file
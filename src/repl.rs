@@ -1,25 +1,183 @@
-use std::io::{Stdin, Stdout, Write};
+use std::io::{BufRead, Write};
+use crate::ast::{Program, StatementNode};
+use crate::compiler::Compiler;
+use crate::diagnostics::{self, Diagnostic};
+use crate::environment::{Env, Environment};
+use crate::evaluator;
 use crate::lexer::Lexer;
-use crate::token;
+use crate::macros;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::runner::{Engine, MAX_STACK};
+use crate::token::Position;
+use crate::vm::Vm;
+
+const PROMPT: &str = ">> ";
+const SOURCE_NAME: &str = "<repl>";
+
+/// What a session keeps between lines on each engine.
+enum Session {
+    Eval(Env),
+    Vm(Box<Compiler>, Vm),
+}
+
+impl Session {
+    fn new(engine: Engine) -> Self {
+        match engine {
+            Engine::Eval => Session::Eval(Environment::new()),
+            Engine::Vm => Session::Vm(Box::default(), Vm::new()),
+        }
+    }
+
+    /// Runs one line's program, returning its value or the diagnostic for
+    /// the error that stopped it.
+    fn run(&mut self, program: &Program) -> Result<Object, Box<Diagnostic>> {
+        let diagnostic = match self {
+            Session::Eval(env) => match evaluator::with_max_stack(MAX_STACK, || evaluator::eval(program, env)) {
+                Object::Error(err) => Diagnostic::from(&err),
+                value => return Ok(value),
+            },
+            Session::Vm(compiler, vm) => match compiler.compile(program) {
+                Ok(bytecode) => match vm.run(bytecode) {
+                    Ok(value) => return Ok(value),
+                    Err(err) => Diagnostic::from(&err),
+                },
+                Err(err) => Diagnostic::from(&err),
+            },
+        };
+        Err(Box::new(diagnostic))
+    }
+}
+
+pub fn start<R: BufRead, W: Write>(mut input: R, mut output: W, engine: Engine, color: bool) {
+    let mut session = Session::new(engine);
+    // macros are expanded the same way whichever engine runs the code
+    let macros = Environment::new();
+    // everything entered so far, so errors raised by code from earlier lines
+    // can still be shown in context
+    let mut history = String::new();
+    let mut line_number = 1;
 
-pub fn start(stdin: Stdin, mut stdout: Stdout) {
     loop {
-        write!(stdout, ">> ").expect("should have written prompt string >>");
-        stdout.flush().expect("should have flushed stdout");
+        write!(output, "{PROMPT}").expect("should have written prompt string >>");
+        output.flush().expect("should have flushed stdout");
 
-        let mut input = String::new();
-        if let Err(e) = stdin.read_line(&mut input) {
-            writeln!(stdout, "Error: {e}").expect("should have written error message");
-            return;
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => {
+                writeln!(output, "Error: {e}").expect("should have written error message");
+                return;
+            }
         }
 
-        let mut lexer = Lexer::new(&input);
-        loop {
-            let token = lexer.next_token();
-            if token.kind == token::TokenKind::EOF {
-                break;
+        let start = Position { line: line_number, column: 1, offset: history.len() };
+        history.push_str(&line);
+        line_number += 1;
+
+        let lexer = Lexer::new_at(&line, start);
+        let mut parser = Parser::new(lexer);
+        let program = match parser.parse_program() {
+            Ok(program) => program,
+            Err(errors) => {
+                for err in &errors {
+                    print_diagnostic(&mut output, &Diagnostic::from(err), &history, color);
+                }
+                continue;
+            }
+        };
+        let program = match evaluator::with_max_stack(MAX_STACK, || macros::expand(program, &macros)) {
+            Ok(program) => program,
+            Err(err) => {
+                print_diagnostic(&mut output, &Diagnostic::from(&err), &history, color);
+                continue;
+            }
+        };
+
+        let evaluated = match session.run(&program) {
+            Ok(value) => value,
+            Err(diagnostic) => {
+                print_diagnostic(&mut output, &diagnostic, &history, color);
+                continue;
             }
-            writeln!(stdout, "{token:?}").expect("should have written token");
+        };
+
+        // a trailing `let` has no value worth echoing back
+        if matches!(program.statements.last(), None | Some(StatementNode::Let(_))) {
+            continue;
+        }
+
+        writeln!(output, "{}", evaluated.inspect()).expect("should have written result");
+    }
+}
+
+fn print_diagnostic<W: Write>(output: &mut W, diagnostic: &Diagnostic, source: &str, color: bool) {
+    let rendered = diagnostics::render(diagnostic, SOURCE_NAME, source, color);
+    write!(output, "{rendered}").expect("should have written diagnostic");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(input: &str) -> String {
+        run_on(Engine::Eval, input)
+    }
+
+    fn run_on(engine: Engine, input: &str) -> String {
+        let mut output = Vec::new();
+        start(input.as_bytes(), &mut output, engine, false);
+        String::from_utf8(output).expect("repl output should be valid utf-8")
+    }
+
+    #[test]
+    fn test_bindings_persist_across_lines() {
+        let output = run("let x = 5;\nlet double = fn(n) { n * 2 };\ndouble(x) + 1\n");
+
+        assert_eq!(output, ">> >> >> 11\n>> ");
+    }
+
+    #[test]
+    fn test_parser_errors_are_rendered() {
+        let output = run("let x = 1;\nlet x 5;\n");
+
+        assert_eq!(output, concat!(
+            ">> >> error[E0001]: expected `=`, found integer\n",
+            " --> <repl>:2:7\n",
+            "  |\n",
+            "2 | let x 5;\n",
+            "  |       ^ expected `=`\n",
+            ">> ",
+        ));
+    }
+
+    #[test]
+    fn test_runtime_errors_point_into_earlier_lines() {
+        let output = run("let half = fn(n) { n / 0 };\nhalf(4)\n");
+
+        assert_eq!(output, concat!(
+            ">> >> error[E0100]: division by zero\n",
+            " --> <repl>:1:22\n",
+            "  |\n",
+            "1 | let half = fn(n) { n / 0 };\n",
+            "  |                      ^\n",
+            ">> ",
+        ));
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        let inputs = [
+            "let x = 5;\nlet double = fn(n) { n * 2 };\ndouble(x) + 1\n",
+            "let add = fn(a) { fn(b) { a + b } };\nlet inc = add(1);\ninc(41)\n[inc(1), {\"a\": inc}[\"a\"](2)]\n",
+            "let half = fn(n) { n / 0 };\nhalf(4)\nlet x 5;\nlen(half)\nmissing\n",
+            "let x = 1;\nlet f = fn() { x };\nlet x = 2;\nf()\n",
+            "let double = macro(x) { quote(unquote(x) * 2) };\ndouble(21)\ndouble()\n",
+        ];
+
+        for input in inputs {
+            assert_eq!(run_on(Engine::Vm, input), run_on(Engine::Eval, input), "engines disagree on {:?}", input);
         }
     }
-}
\ No newline at end of file
+}
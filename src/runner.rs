@@ -0,0 +1,229 @@
+use std::io::Write;
+use std::process::ExitCode;
+use std::str::FromStr;
+use crate::ast::Program;
+use crate::compiler::Compiler;
+use crate::diagnostics::{self, Diagnostic};
+use crate::environment::Environment;
+use crate::evaluator;
+use crate::formatter;
+use crate::lexer::Lexer;
+use crate::macros;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::vm::Vm;
+
+/// How much native stack the binary gives `run` and the REPL. The evaluator
+/// recurses natively for every Monkey call, and half of this is room for the
+/// deepest recursion it allows, even in a debug build.
+pub const STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// how much of it evaluation may use, leaving the rest for everything else
+pub(crate) const MAX_STACK: usize = STACK_SIZE / 2;
+
+/// Which backend runs a program: the tree-walking evaluator or the
+/// bytecode compiler and virtual machine. Both should behave the same.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    #[default]
+    Eval,
+    Vm,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "eval" => Ok(Engine::Eval),
+            "vm" => Ok(Engine::Vm),
+            _ => Err(format!("unknown engine `{s}`, expected `eval` or `vm`")),
+        }
+    }
+}
+
+/// Parses and runs a whole program on `engine`, rendering any parse, compile
+/// or runtime error to `errors` against `name` (the file path, or `<stdin>`).
+/// It expects a thread with [`STACK_SIZE`] bytes of stack.
+pub fn run<W: Write>(name: &str, source: &str, engine: Engine, errors: &mut W, color: bool) -> ExitCode {
+    let Some(program) = parse_and_expand(name, source, errors, color) else {
+        return ExitCode::FAILURE;
+    };
+
+    let diagnostic = match engine {
+        Engine::Eval => match evaluator::with_max_stack(MAX_STACK, || evaluator::eval(&program, &Environment::new())) {
+            Object::Error(err) => Diagnostic::from(&err),
+            _ => return ExitCode::SUCCESS,
+        },
+        Engine::Vm => match Compiler::new().compile(&program) {
+            Ok(bytecode) => match Vm::new().run(bytecode) {
+                Ok(_) => return ExitCode::SUCCESS,
+                Err(err) => Diagnostic::from(&err),
+            },
+            Err(err) => Diagnostic::from(&err),
+        },
+    };
+
+    let rendered = diagnostics::render(&diagnostic, name, source, color);
+    write!(errors, "{rendered}").expect("should have written error");
+    ExitCode::FAILURE
+}
+
+/// Compiles a whole program and writes its disassembled bytecode to
+/// `output`, for debugging the compiler.
+pub fn disassemble<W: Write, E: Write>(name: &str, source: &str, output: &mut W, errors: &mut E, color: bool) -> ExitCode {
+    let Some(program) = parse_and_expand(name, source, errors, color) else {
+        return ExitCode::FAILURE;
+    };
+
+    match Compiler::new().compile(&program) {
+        Ok(bytecode) => {
+            write!(output, "{bytecode}").expect("should have written bytecode");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            let rendered = diagnostics::render(&Diagnostic::from(&err), name, source, color);
+            write!(errors, "{rendered}").expect("should have written compile error");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parses `source` and prints it back in canonical form, or renders its
+/// parse errors to `errors`. Macros are left unexpanded.
+pub fn format<W: Write>(name: &str, source: &str, errors: &mut W, color: bool) -> Option<String> {
+    let program = parse(name, source, errors, color)?;
+    Some(formatter::format(&program, source))
+}
+
+/// Parses `source` and expands its macros, rendering any error to `errors`.
+fn parse_and_expand<W: Write>(name: &str, source: &str, errors: &mut W, color: bool) -> Option<Program> {
+    let program = parse(name, source, errors, color)?;
+
+    match evaluator::with_max_stack(MAX_STACK, || macros::expand(program, &Environment::new())) {
+        Ok(program) => Some(program),
+        Err(err) => {
+            let rendered = diagnostics::render(&Diagnostic::from(&err), name, source, color);
+            write!(errors, "{rendered}").expect("should have written macro error");
+            None
+        }
+    }
+}
+
+fn parse<W: Write>(name: &str, source: &str, errors: &mut W, color: bool) -> Option<Program> {
+    let lexer = Lexer::new(source);
+    let mut parser = Parser::new(lexer);
+
+    match parser.parse_program() {
+        Ok(program) => Some(program),
+        Err(parse_errors) => {
+            for err in &parse_errors {
+                let rendered = diagnostics::render(&Diagnostic::from(err), name, source, color);
+                write!(errors, "{rendered}").expect("should have written parse error");
+            }
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_source(source: &str) -> (ExitCode, String) {
+        let mut errors = Vec::new();
+        let code = run("script.mk", source, Engine::Eval, &mut errors, false);
+        (code, String::from_utf8(errors).expect("error output should be valid utf-8"))
+    }
+
+    #[test]
+    fn test_successful_program() {
+        let (code, errors) = run_source("let add = fn(a, b) { a + b }; add(1, 2);");
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(errors, "");
+    }
+
+    #[test]
+    fn test_parse_errors_fail() {
+        let (code, errors) = run_source("let x 5;\nlet = 1;");
+
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(errors.starts_with(concat!(
+            "error[E0001]: expected `=`, found integer\n",
+            " --> script.mk:1:7\n",
+            "  |\n",
+            "1 | let x 5;\n",
+            "  |       ^ expected `=`\n",
+            "error[E0001]: expected identifier, found `=`\n",
+            " --> script.mk:2:5\n",
+        )), "unexpected error output:\n{errors}");
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+        let code = disassemble("script.mk", "1 + 2", &mut output, &mut errors, false);
+
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert_eq!(String::from_utf8(output).unwrap(), "0000 OpConstant 0\n0003 OpConstant 1\n0006 OpAdd (1:3)\n");
+        assert!(errors.is_empty());
+
+        let code = disassemble("script.mk", "let = 1;", &mut Vec::new(), &mut errors, false);
+        assert_eq!(code, ExitCode::FAILURE);
+        assert!(String::from_utf8(errors).unwrap().starts_with("error[E0001]: expected identifier, found `=`\n"));
+    }
+
+    #[test]
+    fn test_format() {
+        let mut errors = Vec::new();
+        let source = "let twice = macro(x) { quote(unquote(x) * 2) };\ntwice( 1+2 )";
+        let formatted = format("script.mk", source, &mut errors, false);
+
+        assert_eq!(formatted.as_deref(), Some("let twice = macro(x) { quote(unquote(x) * 2) };\ntwice(1 + 2);\n"));
+        assert!(errors.is_empty());
+
+        assert_eq!(format("script.mk", "let = 1;", &mut errors, false), None);
+        assert!(String::from_utf8(errors).unwrap().starts_with("error[E0001]: expected identifier, found `=`\n"));
+    }
+
+    #[test]
+    fn test_runtime_error_fails() {
+        let (code, errors) = run_source("let x = 5;\nx + true;\nx;");
+
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(errors, concat!(
+            "error[E0100]: type mismatch: INTEGER + BOOLEAN\n",
+            " --> script.mk:2:3\n",
+            "  |\n",
+            "2 | x + true;\n",
+            "  |   ^\n",
+        ));
+    }
+
+    #[test]
+    fn test_vm_engine() {
+        let mut errors = Vec::new();
+        let code = run("script.mk", "let add = fn(a, b) { a + b }; add(1, 2);", Engine::Vm, &mut errors, false);
+        assert_eq!(code, ExitCode::SUCCESS);
+        assert!(errors.is_empty());
+
+        let code = run("script.mk", "let x = 5;\nx + true;\nx;", Engine::Vm, &mut errors, false);
+        assert_eq!(code, ExitCode::FAILURE);
+        assert_eq!(String::from_utf8(errors).unwrap(), concat!(
+            "error[E0100]: type mismatch: INTEGER + BOOLEAN\n",
+            " --> script.mk:2:3\n",
+            "  |\n",
+            "2 | x + true;\n",
+            "  |   ^\n",
+        ));
+    }
+
+    #[test]
+    fn test_parse_engine() {
+        assert_eq!("eval".parse(), Ok(Engine::Eval));
+        assert_eq!("vm".parse(), Ok(Engine::Vm));
+        assert_eq!("jit".parse::<Engine>(), Err("unknown engine `jit`, expected `eval` or `vm`".to_string()));
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SymbolScope {
+    Global,
+    Local,
+    // captured from an enclosing function when the closure is created
+    Free,
+    // the function being compiled, referring to itself
+    Function,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// The names visible while compiling one function (or the program itself,
+/// for the outermost table) and the slot each is stored in.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    pub num_definitions: usize,
+    /// The enclosing function's symbols this one captures, in the order of
+    /// their `Free` indexes.
+    pub free_symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn new_enclosed(outer: SymbolTable) -> Self {
+        SymbolTable { outer: Some(Box::new(outer)), ..Default::default() }
+    }
+
+    /// Hands back the enclosing table, or `None` for the outermost one.
+    pub fn into_outer(self) -> Option<SymbolTable> {
+        self.outer.map(|outer| *outer)
+    }
+
+    /// Binds `name` in this table. Defining a name again reuses its slot, as
+    /// a second `let` in the same scope replaces the first.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = if self.outer.is_some() { SymbolScope::Local } else { SymbolScope::Global };
+
+        if let Some(symbol) = self.store.get(name).filter(|symbol| symbol.scope == scope) {
+            return symbol.clone();
+        }
+
+        let symbol = Symbol { name: name.to_string(), scope, index: self.num_definitions };
+        self.num_definitions += 1;
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol { name: name.to_string(), scope: SymbolScope::Function, index: 0 };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    /// Defines `name` in the outermost table. Used for names that aren't
+    /// bound anywhere yet: like the evaluator, the VM only looks them up when
+    /// the code runs, so a function may use a global defined after it.
+    pub fn define_global(&mut self, name: &str) -> Symbol {
+        match &mut self.outer {
+            Some(outer) => outer.define_global(name),
+            None => self.define(name),
+        }
+    }
+
+    /// The global names in slot order.
+    pub fn global_names(&self) -> Vec<String> {
+        match &self.outer {
+            Some(outer) => outer.global_names(),
+            None => {
+                let mut symbols: Vec<&Symbol> = self.store.values()
+                    .filter(|symbol| symbol.scope == SymbolScope::Global)
+                    .collect();
+                symbols.sort_by_key(|symbol| symbol.index);
+                symbols.into_iter().map(|symbol| symbol.name.clone()).collect()
+            }
+        }
+    }
+
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        if symbol.scope == SymbolScope::Global {
+            return Some(symbol);
+        }
+        Some(self.define_free(symbol))
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol { name: name.to_string(), scope, index }
+    }
+
+    #[test]
+    fn test_define() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("a"), symbol("a", SymbolScope::Local, 0));
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 1));
+        assert_eq!(local.num_definitions, 2);
+
+        let global = local.into_outer().expect("local table should have an outer one");
+        assert_eq!(global.num_definitions, 2);
+        assert_eq!(global.global_names(), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_resolve_nested() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define("b");
+
+        let mut second = SymbolTable::new_enclosed(first);
+        second.define("c");
+
+        assert_eq!(second.resolve("a"), Some(symbol("a", SymbolScope::Global, 0)));
+        assert_eq!(second.resolve("b"), Some(symbol("b", SymbolScope::Free, 0)));
+        assert_eq!(second.resolve("c"), Some(symbol("c", SymbolScope::Local, 0)));
+        assert_eq!(second.resolve("d"), None);
+        assert_eq!(second.free_symbols, [symbol("b", SymbolScope::Local, 0)]);
+    }
+
+    #[test]
+    fn test_free_symbols_pass_through_scopes() {
+        let mut first = SymbolTable::new_enclosed(SymbolTable::new());
+        first.define("a");
+        let mut third = SymbolTable::new_enclosed(SymbolTable::new_enclosed(first));
+
+        assert_eq!(third.resolve("a"), Some(symbol("a", SymbolScope::Free, 0)));
+        assert_eq!(third.free_symbols, [symbol("a", SymbolScope::Free, 0)]);
+
+        let second = third.into_outer().unwrap();
+        assert_eq!(second.free_symbols, [symbol("a", SymbolScope::Local, 0)]);
+    }
+
+    #[test]
+    fn test_function_name_and_late_globals() {
+        let mut local = SymbolTable::new_enclosed(SymbolTable::new());
+        local.define_function_name("f");
+
+        assert_eq!(local.resolve("f"), Some(symbol("f", SymbolScope::Function, 0)));
+        assert_eq!(local.define("f"), symbol("f", SymbolScope::Local, 0));
+        assert_eq!(local.resolve("f"), Some(symbol("f", SymbolScope::Local, 0)));
+
+        assert_eq!(local.define_global("g"), symbol("g", SymbolScope::Global, 0));
+        assert_eq!(local.resolve("g"), Some(symbol("g", SymbolScope::Global, 0)));
+
+        let mut global = local.into_outer().unwrap();
+        assert_eq!(global.define("g"), symbol("g", SymbolScope::Global, 0));
+    }
+}
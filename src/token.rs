@@ -5,17 +5,49 @@ use std::fmt::Display;
 pub struct Token {
     pub kind: TokenKind,
     pub literal: String,
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq, Default, Clone)]
+/// A location in the source. `line` and `column` are 1-based and count
+/// characters; `offset` is the 0-based byte offset into the source string.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position { line: 1, column: 1, offset: 0 }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The source range a token covers, from `start` up to but not including `end`.
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Default, Clone)]
 pub enum TokenKind {
     #[default]
     Illegal,
     EOF,
+    // only produced by a lexer keeping comments
+    Comment,
 
     // Identifiers + literals
     Ident,
     Int,
+    String,
 
     // Operators
     Assign,
@@ -35,11 +67,14 @@ pub enum TokenKind {
     // Delimiters
     Comma,
     Semicolon,
+    Colon,
 
     LParen,
     RParen,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
 
     // Keywords
     Function,
@@ -49,6 +84,7 @@ pub enum TokenKind {
     If,
     Else,
     Return,
+    Macro,
 }
 
 impl Display for TokenKind {
@@ -56,16 +92,21 @@ impl Display for TokenKind {
         match self {
             TokenKind::Illegal => write!(f, "Illegal"),
             TokenKind::EOF => write!(f, "EOF"),
+            TokenKind::Comment => write!(f, "Comment"),
             TokenKind::Ident => write!(f, "Ident"),
             TokenKind::Int => write!(f, "Int"),
+            TokenKind::String => write!(f, "String"),
             TokenKind::Assign => write!(f, "="),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::Comma => write!(f, ","),
             TokenKind::Semicolon => write!(f, ";"),
+            TokenKind::Colon => write!(f, ":"),
             TokenKind::LParen => write!(f, "("),
             TokenKind::RParen => write!(f, ")"),
             TokenKind::LBrace => write!(f, "{{"),
             TokenKind::RBrace => write!(f, "}}"),
+            TokenKind::LBracket => write!(f, "["),
+            TokenKind::RBracket => write!(f, "]"),
             TokenKind::Function => write!(f, "fn"),
             TokenKind::Let => write!(f, "let"),
             TokenKind::Bang => write!(f, "!"),
@@ -79,12 +120,29 @@ impl Display for TokenKind {
             TokenKind::If => write!(f, "if"),
             TokenKind::Else => write!(f, "else"),
             TokenKind::Return => write!(f, "return"),
+            TokenKind::Macro => write!(f, "macro"),
             TokenKind::Eq => write!(f, "=="),
             TokenKind::NotEq => write!(f, "!="),
         }
     }
 }
 
+impl TokenKind {
+    /// How the token kind reads in an error message: literal-carrying kinds
+    /// by name, everything else as its quoted source text.
+    pub fn describe(&self) -> String {
+        match self {
+            TokenKind::Illegal => "illegal character".to_string(),
+            TokenKind::EOF => "end of input".to_string(),
+            TokenKind::Comment => "comment".to_string(),
+            TokenKind::Ident => "identifier".to_string(),
+            TokenKind::Int => "integer".to_string(),
+            TokenKind::String => "string".to_string(),
+            other => format!("`{}`", other),
+        }
+    }
+}
+
 pub(crate) fn lookup_ident(ident: &str) -> TokenKind {
     match ident {
         "fn" => TokenKind::Function,
@@ -94,6 +152,7 @@ pub(crate) fn lookup_ident(ident: &str) -> TokenKind {
         "if" => TokenKind::If,
         "else" => TokenKind::Else,
         "return" => TokenKind::Return,
+        "macro" => TokenKind::Macro,
         _ => TokenKind::Ident,
     }
 }
\ No newline at end of file
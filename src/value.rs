@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::ast::Node;
+use crate::object::{HashKey, HashPair, Object};
+
+/// A Monkey value as seen from Rust: what [`Interpreter::eval_str`] returns
+/// and what globals and native functions exchange with a script.
+///
+/// [`Interpreter::eval_str`]: crate::Interpreter::eval_str
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Value>),
+    Hash(BTreeMap<HashKey, Value>),
+    Function(FunctionRef),
+}
+
+/// A function defined by a script or registered as a native. It can't be
+/// called from Rust, but can be handed back to the interpreter as a global.
+#[derive(Debug, Clone)]
+pub struct FunctionRef(Object);
+
+// the same function, not merely one with the same source
+impl PartialEq for FunctionRef {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b),
+            (Object::Builtin(a), Object::Builtin(b)) => Rc::ptr_eq(&a.func, &b.func),
+            (Object::Closure(a), Object::Closure(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    pub(crate) fn from_object(object: &Object) -> Value {
+        match object {
+            Object::Integer(value) => Value::Integer(*value),
+            Object::Boolean(value) => Value::Boolean(*value),
+            Object::String(value) => Value::String(value.clone()),
+            Object::Array(elements) => Value::Array(elements.iter().map(Value::from_object).collect()),
+            Object::Hash(pairs) => Value::Hash(pairs.iter()
+                .map(|(key, pair)| (key.clone(), Value::from_object(&pair.value)))
+                .collect()),
+            Object::ReturnValue(value) => Value::from_object(value),
+            // quoted code comes out as its source
+            Object::Quote(node) => Value::String(node.print()),
+            Object::Function(_) | Object::Builtin(_) | Object::CompiledFunction(_) | Object::Closure(_)
+            | Object::Macro(_) => {
+                Value::Function(FunctionRef(object.clone()))
+            }
+            // errors are reported through `Error`, never as values
+            Object::Null | Object::Error(_) => Value::Null,
+        }
+    }
+
+    pub(crate) fn into_object(self) -> Object {
+        match self {
+            Value::Null => Object::Null,
+            Value::Integer(value) => Object::Integer(value),
+            Value::Boolean(value) => Object::Boolean(value),
+            Value::String(value) => Object::String(value),
+            Value::Array(elements) => Object::Array(Rc::new(elements.into_iter().map(Value::into_object).collect())),
+            Value::Hash(pairs) => Object::Hash(Rc::new(pairs.into_iter()
+                .map(|(key, value)| {
+                    let pair = HashPair { key: key_object(&key), value: value.into_object() };
+                    (key, pair)
+                })
+                .collect())),
+            Value::Function(FunctionRef(object)) => object,
+        }
+    }
+}
+
+fn key_object(key: &HashKey) -> Object {
+    match key {
+        HashKey::Integer(value) => Object::Integer(*value),
+        HashKey::Boolean(value) => Object::Boolean(*value),
+        HashKey::String(value) => Object::String(value.clone()),
+    }
+}
+
+/// Prints the value the way the REPL echoes it.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.clone().into_object().inspect())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Integer(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Boolean(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(elements: Vec<Value>) -> Self {
+        Value::Array(elements)
+    }
+}
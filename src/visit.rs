@@ -0,0 +1,502 @@
+//! Traversals over the AST that only need to handle the nodes they care
+//! about.
+//!
+//! Each trait has a method per node kind whose default recurses into the
+//! node's children through the matching `walk_*` function. An override that
+//! still wants the children visited calls that function itself, before or
+//! after its own work. [`Visitor`] borrows the tree, while [`Fold`] takes it
+//! apart and builds a new one, so it can replace any node it likes.
+
+use crate::ast::{ArrayLiteral, BlockStatement, BooleanLiteral, CallExpression, ExpressionNode, ExpressionStatement, FunctionLiteral, HashLiteral, Identifier, IfExpression, IndexExpression, InfixExpression, IntegerLiteral, LetStatement, MacroLiteral, PrefixExpression, Program, ReturnStatement, StatementNode, StringLiteral};
+
+/// Looks at every node of a tree in source order, parents before children.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_statement(&mut self, statement: &StatementNode) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_let_statement(&mut self, stmt: &LetStatement) {
+        walk_let_statement(self, stmt);
+    }
+
+    fn visit_return_statement(&mut self, stmt: &ReturnStatement) {
+        walk_return_statement(self, stmt);
+    }
+
+    fn visit_expression_statement(&mut self, stmt: &ExpressionStatement) {
+        walk_expression_statement(self, stmt);
+    }
+
+    fn visit_block_statement(&mut self, block: &BlockStatement) {
+        walk_block_statement(self, block);
+    }
+
+    fn visit_expression(&mut self, expression: &ExpressionNode) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_identifier(&mut self, _ident: &Identifier) {}
+
+    fn visit_integer_literal(&mut self, _lit: &IntegerLiteral) {}
+
+    fn visit_string_literal(&mut self, _lit: &StringLiteral) {}
+
+    fn visit_boolean_literal(&mut self, _lit: &BooleanLiteral) {}
+
+    fn visit_prefix_expression(&mut self, exp: &PrefixExpression) {
+        walk_prefix_expression(self, exp);
+    }
+
+    fn visit_infix_expression(&mut self, exp: &InfixExpression) {
+        walk_infix_expression(self, exp);
+    }
+
+    fn visit_if_expression(&mut self, exp: &IfExpression) {
+        walk_if_expression(self, exp);
+    }
+
+    fn visit_function_literal(&mut self, func: &FunctionLiteral) {
+        walk_function_literal(self, func);
+    }
+
+    fn visit_macro_literal(&mut self, mac: &MacroLiteral) {
+        walk_macro_literal(self, mac);
+    }
+
+    fn visit_call_expression(&mut self, call: &CallExpression) {
+        walk_call_expression(self, call);
+    }
+
+    fn visit_array_literal(&mut self, array: &ArrayLiteral) {
+        walk_array_literal(self, array);
+    }
+
+    fn visit_index_expression(&mut self, exp: &IndexExpression) {
+        walk_index_expression(self, exp);
+    }
+
+    fn visit_hash_literal(&mut self, hash: &HashLiteral) {
+        walk_hash_literal(self, hash);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for statement in &program.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &StatementNode) {
+    match statement {
+        StatementNode::Let(stmt) => visitor.visit_let_statement(stmt),
+        StatementNode::Return(stmt) => visitor.visit_return_statement(stmt),
+        StatementNode::Expression(stmt) => visitor.visit_expression_statement(stmt),
+    }
+}
+
+pub fn walk_let_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &LetStatement) {
+    visitor.visit_identifier(&stmt.name);
+    if let Some(value) = &stmt.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_return_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ReturnStatement) {
+    if let Some(value) = &stmt.return_value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_expression_statement<V: Visitor + ?Sized>(visitor: &mut V, stmt: &ExpressionStatement) {
+    if let Some(expression) = &stmt.expression {
+        visitor.visit_expression(expression);
+    }
+}
+
+pub fn walk_block_statement<V: Visitor + ?Sized>(visitor: &mut V, block: &BlockStatement) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &ExpressionNode) {
+    match expression {
+        ExpressionNode::IdentifierNode(ident) => visitor.visit_identifier(ident),
+        ExpressionNode::Integer(lit) => visitor.visit_integer_literal(lit),
+        ExpressionNode::String(lit) => visitor.visit_string_literal(lit),
+        ExpressionNode::Boolean(lit) => visitor.visit_boolean_literal(lit),
+        ExpressionNode::Prefix(exp) => visitor.visit_prefix_expression(exp),
+        ExpressionNode::Infix(exp) => visitor.visit_infix_expression(exp),
+        ExpressionNode::If(exp) => visitor.visit_if_expression(exp),
+        ExpressionNode::Function(func) => visitor.visit_function_literal(func),
+        ExpressionNode::Macro(mac) => visitor.visit_macro_literal(mac),
+        ExpressionNode::Call(call) => visitor.visit_call_expression(call),
+        ExpressionNode::Array(array) => visitor.visit_array_literal(array),
+        ExpressionNode::Index(exp) => visitor.visit_index_expression(exp),
+        ExpressionNode::Hash(hash) => visitor.visit_hash_literal(hash),
+    }
+}
+
+pub fn walk_prefix_expression<V: Visitor + ?Sized>(visitor: &mut V, exp: &PrefixExpression) {
+    visitor.visit_expression(&exp.right);
+}
+
+pub fn walk_infix_expression<V: Visitor + ?Sized>(visitor: &mut V, exp: &InfixExpression) {
+    visitor.visit_expression(&exp.left);
+    visitor.visit_expression(&exp.right);
+}
+
+pub fn walk_if_expression<V: Visitor + ?Sized>(visitor: &mut V, exp: &IfExpression) {
+    visitor.visit_expression(&exp.condition);
+    visitor.visit_block_statement(&exp.consequence);
+    if let Some(alternative) = &exp.alternative {
+        visitor.visit_block_statement(alternative);
+    }
+}
+
+pub fn walk_function_literal<V: Visitor + ?Sized>(visitor: &mut V, func: &FunctionLiteral) {
+    for parameter in &func.parameters {
+        visitor.visit_identifier(parameter);
+    }
+    visitor.visit_block_statement(&func.body);
+}
+
+pub fn walk_macro_literal<V: Visitor + ?Sized>(visitor: &mut V, mac: &MacroLiteral) {
+    for parameter in &mac.parameters {
+        visitor.visit_identifier(parameter);
+    }
+    visitor.visit_block_statement(&mac.body);
+}
+
+pub fn walk_call_expression<V: Visitor + ?Sized>(visitor: &mut V, call: &CallExpression) {
+    visitor.visit_expression(&call.function);
+    for argument in &call.arguments {
+        visitor.visit_expression(argument);
+    }
+}
+
+pub fn walk_array_literal<V: Visitor + ?Sized>(visitor: &mut V, array: &ArrayLiteral) {
+    for element in &array.elements {
+        visitor.visit_expression(element);
+    }
+}
+
+pub fn walk_index_expression<V: Visitor + ?Sized>(visitor: &mut V, exp: &IndexExpression) {
+    visitor.visit_expression(&exp.left);
+    visitor.visit_expression(&exp.index);
+}
+
+pub fn walk_hash_literal<V: Visitor + ?Sized>(visitor: &mut V, hash: &HashLiteral) {
+    for (key, value) in &hash.pairs {
+        visitor.visit_expression(key);
+        visitor.visit_expression(value);
+    }
+}
+
+/// Rebuilds a tree from the nodes of another, which it consumes. The
+/// defaults fold every child and put the node back together around them.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        walk_program_fold(self, program)
+    }
+
+    fn fold_statement(&mut self, statement: StatementNode) -> StatementNode {
+        walk_statement_fold(self, statement)
+    }
+
+    fn fold_let_statement(&mut self, stmt: LetStatement) -> LetStatement {
+        walk_let_statement_fold(self, stmt)
+    }
+
+    fn fold_return_statement(&mut self, stmt: ReturnStatement) -> ReturnStatement {
+        walk_return_statement_fold(self, stmt)
+    }
+
+    fn fold_expression_statement(&mut self, stmt: ExpressionStatement) -> ExpressionStatement {
+        walk_expression_statement_fold(self, stmt)
+    }
+
+    fn fold_block_statement(&mut self, block: BlockStatement) -> BlockStatement {
+        walk_block_statement_fold(self, block)
+    }
+
+    fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+        walk_expression_fold(self, expression)
+    }
+
+    fn fold_identifier(&mut self, ident: Identifier) -> Identifier {
+        ident
+    }
+
+    fn fold_integer_literal(&mut self, lit: IntegerLiteral) -> IntegerLiteral {
+        lit
+    }
+
+    fn fold_string_literal(&mut self, lit: StringLiteral) -> StringLiteral {
+        lit
+    }
+
+    fn fold_boolean_literal(&mut self, lit: BooleanLiteral) -> BooleanLiteral {
+        lit
+    }
+
+    fn fold_prefix_expression(&mut self, exp: PrefixExpression) -> PrefixExpression {
+        walk_prefix_expression_fold(self, exp)
+    }
+
+    fn fold_infix_expression(&mut self, exp: InfixExpression) -> InfixExpression {
+        walk_infix_expression_fold(self, exp)
+    }
+
+    fn fold_if_expression(&mut self, exp: IfExpression) -> IfExpression {
+        walk_if_expression_fold(self, exp)
+    }
+
+    fn fold_function_literal(&mut self, func: FunctionLiteral) -> FunctionLiteral {
+        walk_function_literal_fold(self, func)
+    }
+
+    fn fold_macro_literal(&mut self, mac: MacroLiteral) -> MacroLiteral {
+        walk_macro_literal_fold(self, mac)
+    }
+
+    fn fold_call_expression(&mut self, call: CallExpression) -> CallExpression {
+        walk_call_expression_fold(self, call)
+    }
+
+    fn fold_array_literal(&mut self, array: ArrayLiteral) -> ArrayLiteral {
+        walk_array_literal_fold(self, array)
+    }
+
+    fn fold_index_expression(&mut self, exp: IndexExpression) -> IndexExpression {
+        walk_index_expression_fold(self, exp)
+    }
+
+    fn fold_hash_literal(&mut self, hash: HashLiteral) -> HashLiteral {
+        walk_hash_literal_fold(self, hash)
+    }
+}
+
+pub fn walk_program_fold<F: Fold + ?Sized>(folder: &mut F, program: Program) -> Program {
+    Program {
+        statements: program.statements.into_iter().map(|statement| folder.fold_statement(statement)).collect(),
+    }
+}
+
+pub fn walk_statement_fold<F: Fold + ?Sized>(folder: &mut F, statement: StatementNode) -> StatementNode {
+    match statement {
+        StatementNode::Let(stmt) => StatementNode::Let(folder.fold_let_statement(stmt)),
+        StatementNode::Return(stmt) => StatementNode::Return(folder.fold_return_statement(stmt)),
+        StatementNode::Expression(stmt) => StatementNode::Expression(folder.fold_expression_statement(stmt)),
+    }
+}
+
+pub fn walk_let_statement_fold<F: Fold + ?Sized>(folder: &mut F, stmt: LetStatement) -> LetStatement {
+    LetStatement {
+        token: stmt.token,
+        name: folder.fold_identifier(stmt.name),
+        value: stmt.value.map(|value| folder.fold_expression(value)),
+    }
+}
+
+pub fn walk_return_statement_fold<F: Fold + ?Sized>(folder: &mut F, stmt: ReturnStatement) -> ReturnStatement {
+    ReturnStatement {
+        token: stmt.token,
+        return_value: stmt.return_value.map(|value| folder.fold_expression(value)),
+    }
+}
+
+pub fn walk_expression_statement_fold<F: Fold + ?Sized>(folder: &mut F, stmt: ExpressionStatement) -> ExpressionStatement {
+    ExpressionStatement {
+        token: stmt.token,
+        expression: stmt.expression.map(|expression| folder.fold_expression(expression)),
+    }
+}
+
+pub fn walk_block_statement_fold<F: Fold + ?Sized>(folder: &mut F, block: BlockStatement) -> BlockStatement {
+    BlockStatement {
+        token: block.token,
+        statements: block.statements.into_iter().map(|statement| folder.fold_statement(statement)).collect(),
+    }
+}
+
+pub fn walk_expression_fold<F: Fold + ?Sized>(folder: &mut F, expression: ExpressionNode) -> ExpressionNode {
+    match expression {
+        ExpressionNode::IdentifierNode(ident) => ExpressionNode::IdentifierNode(folder.fold_identifier(ident)),
+        ExpressionNode::Integer(lit) => ExpressionNode::Integer(folder.fold_integer_literal(lit)),
+        ExpressionNode::String(lit) => ExpressionNode::String(folder.fold_string_literal(lit)),
+        ExpressionNode::Boolean(lit) => ExpressionNode::Boolean(folder.fold_boolean_literal(lit)),
+        ExpressionNode::Prefix(exp) => ExpressionNode::Prefix(folder.fold_prefix_expression(exp)),
+        ExpressionNode::Infix(exp) => ExpressionNode::Infix(folder.fold_infix_expression(exp)),
+        ExpressionNode::If(exp) => ExpressionNode::If(folder.fold_if_expression(exp)),
+        ExpressionNode::Function(func) => ExpressionNode::Function(folder.fold_function_literal(func)),
+        ExpressionNode::Macro(mac) => ExpressionNode::Macro(folder.fold_macro_literal(mac)),
+        ExpressionNode::Call(call) => ExpressionNode::Call(folder.fold_call_expression(call)),
+        ExpressionNode::Array(array) => ExpressionNode::Array(folder.fold_array_literal(array)),
+        ExpressionNode::Index(exp) => ExpressionNode::Index(folder.fold_index_expression(exp)),
+        ExpressionNode::Hash(hash) => ExpressionNode::Hash(folder.fold_hash_literal(hash)),
+    }
+}
+
+pub fn walk_prefix_expression_fold<F: Fold + ?Sized>(folder: &mut F, exp: PrefixExpression) -> PrefixExpression {
+    PrefixExpression {
+        token: exp.token,
+        operator: exp.operator,
+        right: Box::new(folder.fold_expression(*exp.right)),
+    }
+}
+
+pub fn walk_infix_expression_fold<F: Fold + ?Sized>(folder: &mut F, exp: InfixExpression) -> InfixExpression {
+    InfixExpression {
+        token: exp.token,
+        left: Box::new(folder.fold_expression(*exp.left)),
+        operator: exp.operator,
+        right: Box::new(folder.fold_expression(*exp.right)),
+    }
+}
+
+pub fn walk_if_expression_fold<F: Fold + ?Sized>(folder: &mut F, exp: IfExpression) -> IfExpression {
+    IfExpression {
+        token: exp.token,
+        condition: Box::new(folder.fold_expression(*exp.condition)),
+        consequence: folder.fold_block_statement(exp.consequence),
+        alternative: exp.alternative.map(|block| folder.fold_block_statement(block)),
+    }
+}
+
+pub fn walk_function_literal_fold<F: Fold + ?Sized>(folder: &mut F, func: FunctionLiteral) -> FunctionLiteral {
+    FunctionLiteral {
+        token: func.token,
+        parameters: func.parameters.into_iter().map(|parameter| folder.fold_identifier(parameter)).collect(),
+        body: folder.fold_block_statement(func.body),
+    }
+}
+
+pub fn walk_macro_literal_fold<F: Fold + ?Sized>(folder: &mut F, mac: MacroLiteral) -> MacroLiteral {
+    MacroLiteral {
+        token: mac.token,
+        parameters: mac.parameters.into_iter().map(|parameter| folder.fold_identifier(parameter)).collect(),
+        body: folder.fold_block_statement(mac.body),
+    }
+}
+
+pub fn walk_call_expression_fold<F: Fold + ?Sized>(folder: &mut F, call: CallExpression) -> CallExpression {
+    CallExpression {
+        token: call.token,
+        function: Box::new(folder.fold_expression(*call.function)),
+        arguments: call.arguments.into_iter().map(|argument| folder.fold_expression(argument)).collect(),
+    }
+}
+
+pub fn walk_array_literal_fold<F: Fold + ?Sized>(folder: &mut F, array: ArrayLiteral) -> ArrayLiteral {
+    ArrayLiteral {
+        token: array.token,
+        elements: array.elements.into_iter().map(|element| folder.fold_expression(element)).collect(),
+    }
+}
+
+pub fn walk_index_expression_fold<F: Fold + ?Sized>(folder: &mut F, exp: IndexExpression) -> IndexExpression {
+    IndexExpression {
+        token: exp.token,
+        left: Box::new(folder.fold_expression(*exp.left)),
+        index: Box::new(folder.fold_expression(*exp.index)),
+    }
+}
+
+pub fn walk_hash_literal_fold<F: Fold + ?Sized>(folder: &mut F, hash: HashLiteral) -> HashLiteral {
+    HashLiteral {
+        token: hash.token,
+        pairs: hash.pairs.into_iter()
+            .map(|(key, value)| (folder.fold_expression(key), folder.fold_expression(value)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Node;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> Program {
+        Parser::new(Lexer::new(input)).parse_program().expect("input should parse")
+    }
+
+    // every program here contains each kind of node at least once
+    const EVERY_NODE: &str = r#"let f = fn(x) { return -x[0] + 1; }; if (true) { {"a": [f]} } else { macro(y) { y } }; f(2)"#;
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl Visitor for Recorder {
+        fn visit_identifier(&mut self, ident: &Identifier) {
+            self.0.push(format!("ident {}", ident.value));
+        }
+
+        fn visit_integer_literal(&mut self, lit: &IntegerLiteral) {
+            self.0.push(format!("int {}", lit.value));
+        }
+
+        fn visit_string_literal(&mut self, lit: &StringLiteral) {
+            self.0.push(format!("string {}", lit.value));
+        }
+
+        fn visit_boolean_literal(&mut self, lit: &BooleanLiteral) {
+            self.0.push(format!("bool {}", lit.value));
+        }
+
+        // a node seen on the way in, with its children after it
+        fn visit_expression(&mut self, expression: &ExpressionNode) {
+            if let ExpressionNode::Macro(mac) = expression {
+                self.0.push(format!("macro/{}", mac.parameters.len()));
+            }
+            walk_expression(self, expression);
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_every_node_in_source_order() {
+        let mut recorder = Recorder::default();
+        recorder.visit_program(&parse(EVERY_NODE));
+
+        assert_eq!(recorder.0, [
+            "ident f", "ident x", "ident x", "int 0", "int 1",
+            "bool true", "string a", "ident f",
+            "macro/1", "ident y", "ident y",
+            "ident f", "int 2",
+        ]);
+    }
+
+    // replaces every identifier expression with its name as a string, and
+    // renames parameters and bindings to match
+    struct Stringify;
+
+    impl Fold for Stringify {
+        fn fold_expression(&mut self, expression: ExpressionNode) -> ExpressionNode {
+            match expression {
+                ExpressionNode::IdentifierNode(ident) => ExpressionNode::String(StringLiteral {
+                    token: ident.token,
+                    value: ident.value,
+                }),
+                other => walk_expression_fold(self, other),
+            }
+        }
+
+        fn fold_identifier(&mut self, mut ident: Identifier) -> Identifier {
+            ident.value = ident.value.to_uppercase();
+            ident
+        }
+    }
+
+    #[test]
+    fn test_fold_rebuilds_the_tree() {
+        let program = Stringify.fold_program(parse(EVERY_NODE));
+
+        assert_eq!(program.print(), r#"let F = fn(X) { return ((-("x"[0])) + 1); };if (true) { {"a": ["f"]}; } else { macro(Y) { "y"; }; }"f"(2)"#);
+    }
+}
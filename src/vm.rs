@@ -0,0 +1,575 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use crate::builtins::Registry;
+use crate::code::{read_operand, Opcode};
+use crate::compiler::Bytecode;
+use crate::evaluator::{eval_index_expression, eval_infix_expression, eval_prefix_expression, is_truthy};
+use crate::object::{Closure, CompiledFunction, HashPair, Object, RuntimeError};
+use crate::token::Span;
+
+const STACK_SIZE: usize = 2048;
+const MAX_FRAMES: usize = 1024;
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // where the frame's locals start; the called closure sits just below
+    base_pointer: usize,
+    // the call that pushed the frame, where running out of stack is reported
+    call_span: Span,
+}
+
+/// Runs compiled bytecode. Globals outlive a single run, so a REPL can feed
+/// the VM one compiled line after another.
+pub struct Vm {
+    constants: Vec<Object>,
+    globals: Vec<Option<Object>>,
+    global_names: Vec<String>,
+    builtins: Rc<RefCell<Registry>>,
+
+    stack: Vec<Object>,
+    // the next free slot; the top of the stack is at `sp - 1`
+    sp: usize,
+    frames: Vec<Frame>,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Vm::with_builtins(Rc::new(RefCell::new(Registry::standard())))
+    }
+
+    /// A VM whose builtins come from `builtins` instead of the standard
+    /// registry.
+    pub fn with_builtins(builtins: Rc<RefCell<Registry>>) -> Self {
+        Vm {
+            constants: vec![],
+            globals: vec![],
+            global_names: vec![],
+            builtins,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: 0,
+            frames: Vec::with_capacity(MAX_FRAMES),
+        }
+    }
+
+    /// Runs `bytecode` to the end and returns the value of the program's last
+    /// statement, or of the top-level `return` that stopped it.
+    pub fn run(&mut self, bytecode: Bytecode) -> Result<Object, RuntimeError> {
+        self.constants = bytecode.constants;
+        self.global_names = bytecode.globals;
+        self.globals.resize(self.global_names.len(), None);
+
+        let main = CompiledFunction {
+            instructions: bytecode.instructions,
+            source_map: bytecode.source_map,
+            ..Default::default()
+        };
+        self.sp = 0;
+        self.frames.clear();
+        self.frames.push(Frame {
+            closure: Rc::new(Closure { func: Rc::new(main), free: vec![] }),
+            ip: 0,
+            base_pointer: 0,
+            call_span: Span::default(),
+        });
+
+        let result = self.execute();
+
+        // don't keep the objects of a finished run alive
+        self.stack[..self.sp].fill(Object::Null);
+        self.sp = 0;
+        self.frames.clear();
+        result
+    }
+
+    fn execute(&mut self) -> Result<Object, RuntimeError> {
+        loop {
+            let frame = self.frames.last_mut().expect("there is always a frame while running");
+            let instructions = &frame.closure.func.instructions.0;
+
+            if frame.ip >= instructions.len() {
+                // the end of the main program; functions always return
+                return Ok(if self.sp > 0 { self.pop() } else { Object::Null });
+            }
+
+            let ip = frame.ip;
+            let op = Opcode::from_byte(instructions[ip]).expect("compiler only emits known opcodes");
+            let widths = op.definition().operand_widths;
+            let operands: [usize; 2] = match widths {
+                [] => [0, 0],
+                [a] => [read_operand(&instructions[ip + 1..], *a), 0],
+                [a, b] => [
+                    read_operand(&instructions[ip + 1..], *a),
+                    read_operand(&instructions[ip + 1 + a..], *b),
+                ],
+                _ => unreachable!("no opcode takes more than two operands"),
+            };
+            frame.ip += 1 + widths.iter().sum::<usize>();
+
+            match op {
+                Opcode::Constant => {
+                    let constant = self.constants[operands[0]].clone();
+                    self.push(constant, ip)?;
+                }
+                Opcode::Pop => {
+                    self.pop();
+                }
+                Opcode::True => self.push(Object::Boolean(true), ip)?,
+                Opcode::False => self.push(Object::Boolean(false), ip)?,
+                Opcode::Null => self.push(Object::Null, ip)?,
+
+                Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Div
+                | Opcode::Equal | Opcode::NotEqual | Opcode::LessThan | Opcode::GreaterThan => {
+                    let operator = match op {
+                        Opcode::Add => "+",
+                        Opcode::Sub => "-",
+                        Opcode::Mul => "*",
+                        Opcode::Div => "/",
+                        Opcode::Equal => "==",
+                        Opcode::NotEqual => "!=",
+                        Opcode::LessThan => "<",
+                        _ => ">",
+                    };
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = eval_infix_expression(self.span(ip), operator, left, right);
+                    self.push_result(result, ip)?;
+                }
+                Opcode::Minus | Opcode::Bang => {
+                    let operator = if op == Opcode::Minus { "-" } else { "!" };
+                    let right = self.pop();
+                    let result = eval_prefix_expression(self.span(ip), operator, right);
+                    self.push_result(result, ip)?;
+                }
+
+                Opcode::JumpNotTruthy => {
+                    let condition = self.pop();
+                    if !is_truthy(&condition) {
+                        self.frame().ip = operands[0];
+                    }
+                }
+                Opcode::Jump => self.frame().ip = operands[0],
+
+                Opcode::SetGlobal => {
+                    let value = self.pop();
+                    self.globals[operands[0]] = Some(value);
+                }
+                Opcode::GetGlobal => {
+                    let value = self.get_global(operands[0], ip)?;
+                    self.push(value, ip)?;
+                }
+                Opcode::SetLocal => {
+                    let slot = self.frame().base_pointer + operands[0];
+                    self.stack[slot] = self.pop();
+                }
+                Opcode::GetLocal => {
+                    let slot = self.frame().base_pointer + operands[0];
+                    let value = self.stack[slot].clone();
+                    self.push(value, ip)?;
+                }
+                Opcode::GetFree => {
+                    let value = self.frame().closure.free[operands[0]].clone();
+                    self.push(value, ip)?;
+                }
+                Opcode::CurrentClosure => {
+                    let closure = Rc::clone(&self.frame().closure);
+                    self.push(Object::Closure(closure), ip)?;
+                }
+
+                Opcode::Array => {
+                    let elements = self.stack[self.sp - operands[0]..self.sp].to_vec();
+                    self.sp -= operands[0];
+                    self.push(Object::Array(Rc::new(elements)), ip)?;
+                }
+                Opcode::Hash => {
+                    let hash = self.build_hash(operands[0], ip);
+                    self.sp -= operands[0];
+                    self.push_result(hash, ip)?;
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    let result = eval_index_expression(self.span(ip), left, index);
+                    self.push_result(result, ip)?;
+                }
+
+                Opcode::Call => self.call(operands[0], ip)?,
+                Opcode::ReturnValue | Opcode::Return => {
+                    let value = if op == Opcode::ReturnValue { self.pop() } else { Object::Null };
+                    let frame = self.frames.pop().expect("should be returning from a frame");
+
+                    if self.frames.is_empty() {
+                        // a `return` in the main program ends it
+                        return Ok(value);
+                    }
+                    self.sp = frame.base_pointer - 1;
+                    self.push(value, ip)?;
+                }
+                Opcode::Closure => {
+                    let func = match &self.constants[operands[0]] {
+                        Object::CompiledFunction(func) => Rc::clone(func),
+                        other => unreachable!("closure constant is not a function: {:?}", other),
+                    };
+                    let free = self.stack[self.sp - operands[1]..self.sp].to_vec();
+                    self.sp -= operands[1];
+                    self.push(Object::Closure(Rc::new(Closure { func, free })), ip)?;
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, num_args: usize, ip: usize) -> Result<(), RuntimeError> {
+        let callee = self.stack[self.sp - 1 - num_args].clone();
+
+        match callee {
+            Object::Closure(closure) => {
+                let func = &closure.func;
+                if func.num_parameters != num_args {
+                    return Err(self.error(ip, format!(
+                        "wrong number of arguments: want={}, got={}",
+                        func.num_parameters,
+                        num_args
+                    )));
+                }
+
+                let base_pointer = self.sp - num_args;
+                if self.frames.len() == MAX_FRAMES || base_pointer + func.num_locals > STACK_SIZE {
+                    return Err(self.error(ip, "stack overflow".to_string()));
+                }
+
+                // parameters are the first locals; clear the rest of a
+                // previous call's leftovers
+                self.stack[base_pointer + num_args..base_pointer + func.num_locals].fill(Object::Null);
+                self.sp = base_pointer + func.num_locals;
+                let call_span = self.span(ip);
+                self.frames.push(Frame { closure, ip: 0, base_pointer, call_span });
+                Ok(())
+            }
+            Object::Builtin(builtin) => {
+                let result = (builtin.func)(&self.stack[self.sp - num_args..self.sp]);
+                self.sp -= num_args + 1;
+
+                match result {
+                    Ok(value) => self.push(value, ip),
+                    Err(err) => Err(self.error(ip, err.message)),
+                }
+            }
+            other => Err(self.error(ip, format!("not a function: {}", other.type_name()))),
+        }
+    }
+
+    /// A global that was never set is looked up among the builtins, the
+    /// same fallback the evaluator's environment has.
+    fn get_global(&self, index: usize, ip: usize) -> Result<Object, RuntimeError> {
+        if let Some(value) = &self.globals[index] {
+            return Ok(value.clone());
+        }
+
+        let name = &self.global_names[index];
+        match self.builtins.borrow().lookup(name) {
+            Some(builtin) => Ok(builtin),
+            None => Err(self.error(ip, format!("identifier not found: {}", name))),
+        }
+    }
+
+    /// Builds a hash from the `len` keys and values on top of the stack,
+    /// leaving them there.
+    fn build_hash(&self, len: usize, ip: usize) -> Object {
+        let mut pairs = BTreeMap::new();
+
+        for pair in self.stack[self.sp - len..self.sp].chunks(2) {
+            let (key, value) = (pair[0].clone(), pair[1].clone());
+            let hash_key = match key.hash_key() {
+                Some(hash_key) => hash_key,
+                None => return Object::Error(self.error(ip, format!("unusable as hash key: {}", key.type_name()))),
+            };
+            pairs.insert(hash_key, HashPair { key, value });
+        }
+
+        Object::Hash(Rc::new(pairs))
+    }
+
+    fn push(&mut self, object: Object, ip: usize) -> Result<(), RuntimeError> {
+        if self.sp >= STACK_SIZE {
+            // most pushes have no span of their own
+            let frame = self.frames.last().expect("there is always a frame while running");
+            let span = frame.closure.func.source_map.get(ip).unwrap_or(frame.call_span);
+            return Err(RuntimeError::new(span, "stack overflow".to_string()));
+        }
+
+        self.stack[self.sp] = object;
+        self.sp += 1;
+        Ok(())
+    }
+
+    fn push_result(&mut self, result: Object, ip: usize) -> Result<(), RuntimeError> {
+        match result {
+            Object::Error(err) => Err(err),
+            value => self.push(value, ip),
+        }
+    }
+
+    fn pop(&mut self) -> Object {
+        self.sp -= 1;
+        std::mem::replace(&mut self.stack[self.sp], Object::Null)
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("there is always a frame while running")
+    }
+
+    /// Where the instruction at `ip` in the current frame came from.
+    fn span(&self, ip: usize) -> Span {
+        let frame = self.frames.last().expect("there is always a frame while running");
+        frame.closure.func.source_map.get(ip).unwrap_or_default()
+    }
+
+    fn error(&self, ip: usize, message: String) -> RuntimeError {
+        RuntimeError::new(self.span(ip), message)
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn run(input: &str) -> Result<Object, RuntimeError> {
+        let program = Parser::new(Lexer::new(input)).parse_program().expect("input should parse");
+        let bytecode = Compiler::new().compile(&program).expect("input should compile");
+
+        Vm::new().run(bytecode)
+    }
+
+    fn run_vm_tests(tests: &[(&str, &str)]) {
+        for (input, expected) in tests {
+            match run(input) {
+                Ok(result) => assert_eq!(result.inspect(), *expected, "wrong result for {}", input),
+                Err(err) => panic!("vm error for {}: {}", input, err.message),
+            }
+        }
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        run_vm_tests(&[
+            ("1", "1"),
+            ("1 + 2", "3"),
+            ("1 - 2", "-1"),
+            ("4 / 2", "2"),
+            ("50 / 2 * 2 + 10 - 5", "55"),
+            ("5 * (2 + 10)", "60"),
+            ("-5", "-5"),
+            ("-50 + 100 + -50", "0"),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50"),
+        ]);
+    }
+
+    #[test]
+    fn test_boolean_expressions() {
+        run_vm_tests(&[
+            ("true", "true"),
+            ("1 < 2", "true"),
+            ("1 > 2", "false"),
+            ("1 == 1", "true"),
+            ("1 != 1", "false"),
+            ("true == false", "false"),
+            ("(1 < 2) == true", "true"),
+            ("!true", "false"),
+            ("!5", "false"),
+            ("!!5", "true"),
+            ("!(if (false) { 5; })", "true"),
+            (r#""a" < "b""#, "true"),
+        ]);
+    }
+
+    #[test]
+    fn test_conditionals() {
+        run_vm_tests(&[
+            ("if (true) { 10 }", "10"),
+            ("if (true) { 10 } else { 20 }", "10"),
+            ("if (false) { 10 } else { 20 } ", "20"),
+            ("if (1 < 2) { 10 }", "10"),
+            ("if (1 > 2) { 10 }", "null"),
+            ("if ((if (false) { 10 })) { 10 } else { 20 }", "20"),
+            ("if (true) { let x = 1; }", "null"),
+        ]);
+    }
+
+    #[test]
+    fn test_global_let_statements() {
+        run_vm_tests(&[
+            ("let one = 1; one", "1"),
+            ("let one = 1; let two = one + one; one + two", "3"),
+            ("let one = 1; let one = one + 1; one", "2"),
+            ("let one = 1;", "null"),
+            ("1; let two = 2;", "null"),
+        ]);
+    }
+
+    #[test]
+    fn test_strings_arrays_and_hashes() {
+        run_vm_tests(&[
+            (r#""mon" + "key" + "banana""#, "monkeybanana"),
+            ("[1 + 2, 3 * 4]", "[3, 12]"),
+            ("[]", "[]"),
+            (r#"{"b": 2, 1: [1], true: {}}"#, "{1: [1], true: {}, b: 2}"),
+            ("{1: 1, 1: 2}[1]", "2"),
+            ("[1, 2, 3][1 + 1]", "3"),
+            ("[[1, 1, 1]][0][0]", "1"),
+            ("[1, 2, 3][99]", "null"),
+            ("[1][-1]", "null"),
+            (r#"{"a": 1}["b"]"#, "null"),
+        ]);
+    }
+
+    #[test]
+    fn test_calling_functions() {
+        run_vm_tests(&[
+            ("let fivePlusTen = fn() { 5 + 10; }; fivePlusTen();", "15"),
+            ("let a = fn() { 1 }; let b = fn() { a() + 1 }; b() + b()", "4"),
+            ("let early = fn() { return 99; 100; }; early();", "99"),
+            ("let noReturn = fn() { }; noReturn();", "null"),
+            ("let justLet = fn() { let x = 1; }; justLet();", "null"),
+            ("let returnsOne = fn() { 1; }; let returnsOneReturner = fn() { returnsOne; }; returnsOneReturner()();", "1"),
+            ("let sum = fn(a, b) { let c = a + b; c; }; sum(1, 2) + sum(3, 4);", "10"),
+            ("let globalNum = 10; let f = fn(a) { let n = 5; a + n + globalNum }; f(1) + f(2)", "33"),
+            ("let later = fn() { defined }; let defined = 7; later()", "7"),
+            ("return 1; 2", "1"),
+            ("if (true) { return 3; } 4", "3"),
+        ]);
+    }
+
+    #[test]
+    fn test_closures() {
+        run_vm_tests(&[
+            ("let newClosure = fn(a) { fn() { a; }; }; let closure = newClosure(99); closure();", "99"),
+            ("let newAdder = fn(a, b) { fn(c) { a + b + c }; }; let adder = newAdder(1, 2); adder(8);", "11"),
+            (r#"
+            let newAdderOuter = fn(a, b) {
+                let c = a + b;
+                fn(d) {
+                    let e = d + c;
+                    fn(f) { e + f; };
+                };
+            };
+            let newAdderInner = newAdderOuter(1, 2);
+            let adder = newAdderInner(3);
+            adder(8);
+            "#, "14"),
+            ("fn(x) { x * 2 }", "fn(x) { (x * 2); }"),
+        ]);
+    }
+
+    #[test]
+    fn test_recursive_functions() {
+        run_vm_tests(&[
+            ("let countDown = fn(x) { if (x == 0) { return 0; } else { countDown(x - 1); } }; countDown(1);", "0"),
+            (r#"
+            let wrapper = fn() {
+                let countDown = fn(x) { if (x == 0) { return 0; } else { countDown(x - 1); } };
+                countDown(1);
+            };
+            wrapper();
+            "#, "0"),
+            (r#"
+            let fibonacci = fn(x) {
+                if (x < 2) { return x; }
+                fibonacci(x - 1) + fibonacci(x - 2);
+            };
+            fibonacci(15);
+            "#, "610"),
+        ]);
+    }
+
+    #[test]
+    fn test_builtins() {
+        run_vm_tests(&[
+            (r#"len("four")"#, "4"),
+            ("len([1, 2, 3])", "3"),
+            ("first([1, 2])", "1"),
+            ("rest([1, 2, 3])", "[2, 3]"),
+            ("push([], 1)", "[1]"),
+            ("type(fn() { 1 })", "FUNCTION"),
+            ("let len = fn(x) { 42 }; len([])", "42"),
+            ("let f = fn(arr) { len(arr) }; f([1, 2])", "2"),
+        ]);
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let tests = [
+            ("5 + true;", "type mismatch: INTEGER + BOOLEAN", "1:3"),
+            ("-true", "unknown operator: -BOOLEAN", "1:1"),
+            ("let f = fn() {\n  true + false\n}; f()", "unknown operator: BOOLEAN + BOOLEAN", "2:8"),
+            ("foobar", "identifier not found: foobar", "1:1"),
+            ("let f = fn() { later }; f()", "identifier not found: later", "1:16"),
+            ("1 / 0", "division by zero", "1:3"),
+            ("fn(x) { x }(1, 2)", "wrong number of arguments: want=1, got=2", "1:12"),
+            ("let x = 1; x()", "not a function: INTEGER", "1:13"),
+            ("len(1)", "argument to `len` not supported, got INTEGER", "1:4"),
+            ("1[0]", "index operator not supported: INTEGER", "1:2"),
+            ("{[1]: 2}", "unusable as hash key: ARRAY", "1:1"),
+            (r#"{"a": 1}[fn(x) { x }]"#, "unusable as hash key: FUNCTION", "1:9"),
+        ];
+
+        for (input, message, position) in tests {
+            match run(input) {
+                Err(err) => {
+                    assert_eq!(err.message, message, "wrong error message for {}", input);
+                    assert_eq!(err.span.start.to_string(), position, "wrong error position for {}", input);
+                }
+                Ok(result) => panic!("no error for {}. got {}", input, result.inspect()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stack_overflow_is_an_error() {
+        // both run out at the recursive call, one on frames and the other
+        // on stack slots
+        let tests = [
+            ("let f = fn(x) { f(x + 1) }; f(0)", "1:18"),
+            ("let f = fn(x) { 1 + f(x) }; f(0)", "1:22"),
+        ];
+
+        for (input, position) in tests {
+            match run(input) {
+                Err(err) => {
+                    assert_eq!(err.message, "stack overflow", "wrong error for {}", input);
+                    assert_eq!(err.span.start.to_string(), position, "wrong error position for {}", input);
+                }
+                Ok(result) => panic!("no error for {}. got {}", input, result.inspect()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_globals_persist_between_runs() {
+        let mut compiler = Compiler::new();
+        let mut vm = Vm::new();
+
+        for (input, expected) in [("let a = 1;", "null"), ("let b = a + 1; b * 10", "20"), ("missing", "")] {
+            let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+            let result = vm.run(compiler.compile(&program).unwrap());
+
+            match result {
+                Ok(value) => assert_eq!(value.inspect(), expected, "wrong result for {}", input),
+                Err(err) => assert_eq!(err.message, "identifier not found: missing"),
+            }
+        }
+
+        // an error part way leaves the VM usable
+        let program = Parser::new(Lexer::new("a + b")).parse_program().unwrap();
+        assert_eq!(vm.run(compiler.compile(&program).unwrap()).unwrap().inspect(), "3");
+    }
+}
@@ -0,0 +1,103 @@
+//! Runs every program in `tests/programs`, plus the snippets below, on both
+//! engines and checks that they print the same output and fail with the
+//! same errors, so the evaluator and the VM can't drift apart.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const ENGINES: [&str; 2] = ["eval", "vm"];
+
+// small programs that each stop at a different runtime error, then a few in
+// forms the formatter rewrites, so the fixtures can stay formatted
+const SNIPPETS: &[&str] = &[
+    "puts(1);\n-true",
+    "5 + true;",
+    "\"a\" - \"b\"",
+    "1 / 0",
+    "9223372036854775807 + 1",
+    "foobar",
+    "let f = fn() { later }; f()",
+    "fn(x) { x }(1, 2)",
+    "let x = 1; x()",
+    "len(1)",
+    "len(\"a\", \"b\")",
+    "push(1, 2)",
+    "1[0]",
+    "{[1]: 2}",
+    "{\"a\": 1}[fn(x) { x }]",
+    "let f = fn(x) { if (x > 0) { f(x - 1) } else { x + \"a\" } }; f(3)",
+    "let x = if (true) { 1 } else { y }; x + z",
+    "puts(\"a\", [1, fn(x) { x }], {1: \"b\"});\nreturn puts;",
+    "fn() { puts(z); let z = 1; }()",
+    "let f = fn(x) { f(x + 1) }; f(0)",
+    "let f = fn(x) { 1 + f(x) }; f(0)",
+    // a `;` after a block's value
+    "let adder = fn(a) { fn(b) { a + b }; };\nputs(adder(1)(2), fn() { \"small\"; }(), fn() { }());",
+    "let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, first(arr) * 2)) } };\nputs(fn(arr) { iter(arr, []); }([1, 2]));",
+    // `unquote(x);` inside a quote, and `!(x)`
+    "let unless = macro(condition, consequence, alternative) {\n    quote(if (!(unquote(condition))) {\n        unquote(consequence);\n    } else {\n        unquote(alternative);\n    });\n};\nunless(10 > 5, puts(\"not greater\"), puts(\"greater\"));",
+];
+
+fn run(engine: &str, path: &str, stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkey_lang"))
+        .arg(format!("--engine={engine}"))
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("should have started monkey_lang");
+
+    // the program may exit without reading its input, such as on a bad flag
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().expect("should have run monkey_lang")
+}
+
+fn assert_engines_agree(name: &str, path: &str, stdin: &str) {
+    let [eval, vm] = ENGINES.map(|engine| run(engine, path, stdin));
+
+    assert_eq!(
+        String::from_utf8_lossy(&vm.stdout),
+        String::from_utf8_lossy(&eval.stdout),
+        "engines print different output for {name}",
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&vm.stderr),
+        String::from_utf8_lossy(&eval.stderr),
+        "engines report different errors for {name}",
+    );
+    assert_eq!(vm.status.code(), eval.status.code(), "engines exit differently for {name}");
+}
+
+#[test]
+fn test_programs_agree() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs: Vec<_> = std::fs::read_dir(&dir)
+        .expect("should have read tests/programs")
+        .map(|entry| entry.expect("should have read directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "mk"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no programs in {}", dir.display());
+
+    for program in programs {
+        let path = program.to_str().expect("program path should be valid utf-8");
+        assert_engines_agree(path, path, "");
+    }
+}
+
+#[test]
+fn test_snippets_agree() {
+    for snippet in SNIPPETS {
+        assert_engines_agree(snippet, "-", snippet);
+    }
+}
+
+#[test]
+fn test_unknown_engine_is_rejected() {
+    let output = run("jit", "-", "1");
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("unknown engine `jit`, expected `eval` or `vm`\n"));
+}
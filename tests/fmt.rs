@@ -0,0 +1,78 @@
+//! Runs `monkey_lang fmt` the way CI would: every program in
+//! `tests/programs` that parses must already be formatted.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+fn fmt(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkey_lang"))
+        .arg("fmt")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("should have started monkey_lang");
+
+    // the program may exit without reading its input, such as on a bad flag
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().expect("should have run monkey_lang")
+}
+
+#[test]
+fn test_programs_are_formatted() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths: Vec<String> = std::fs::read_dir(dir)
+        .expect("should have read tests/programs")
+        .map(|entry| entry.unwrap().path().to_string_lossy().into_owned())
+        .filter(|path| !path.ends_with("parse_error.mk"))
+        .collect();
+    paths.sort();
+
+    let mut args = vec!["--check"];
+    args.extend(paths.iter().map(String::as_str));
+    let output = fmt(&args, "");
+
+    assert!(output.status.success(), "unformatted programs:\n{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_format_stdin() {
+    let output = fmt(&["-"], "let add=fn(a,b){a+b};\n\n\nputs(add(1,2))");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "let add = fn(a, b) { a + b };\n\nputs(add(1, 2));\n");
+
+    let output = fmt(&["--check", "-"], "puts( 1 )");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "<stdin>: not formatted\n");
+
+    let output = fmt(&["-"], "let = 1;");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error[E0001]: expected identifier, found `=`\n"));
+}
+
+#[test]
+fn test_format_rewrites_files() {
+    let path = std::env::temp_dir().join(format!("monkey_fmt_{}.mk", std::process::id()));
+    std::fs::write(&path, "let x=if(true){1}else{2}").unwrap();
+
+    let output = fmt(&[path.to_str().unwrap()], "");
+    let formatted = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(formatted, "let x = if (true) { 1 } else { 2 };\n");
+}
+
+#[test]
+fn test_bad_arguments_are_rejected() {
+    for args in [&[][..], &["--write", "-"][..]] {
+        let output = fmt(args, "");
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("usage: monkey_lang"));
+    }
+}
@@ -0,0 +1,6 @@
+let a = 5;
+let b = a * 2 + 10 / 3 - -1;
+puts(a, b, a < b, a > b, a == b, a != b);
+puts(!true, !!a, -(a - b));
+puts("mon" + "key", "a" == "a", "a" != "b");
+puts(if (a > 1) { "big" } else { "small" }, if (false) { 1 });
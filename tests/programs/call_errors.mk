@@ -0,0 +1,4 @@
+let add = fn(a, b) { a + b };
+puts(add(1, 2));
+let apply = fn(f) { f(1) };
+apply(add);
@@ -0,0 +1,18 @@
+let newAdder = fn(a, b) {
+    let c = a + b;
+    fn(d) {
+        let e = d + c;
+        fn(f) { e + f }
+    }
+};
+puts(newAdder(1, 2)(3)(8));
+
+let compose = fn(f, g) { fn(x) { g(f(x)) } };
+let inc = fn(x) { x + 1 };
+let double = fn(x) { x * 2 };
+puts(compose(inc, double)(5), compose(double, inc)(5));
+
+let counter = fn(n) { fn() { n } };
+let counters = [counter(1), counter(2)];
+puts(counters[0]() + counters[1]());
+puts(compose, inc);
@@ -0,0 +1,31 @@
+let map = fn(arr, f) {
+    let iter = fn(arr, acc) {
+        if (len(arr) == 0) {
+            acc
+        } else {
+            iter(rest(arr), push(acc, f(first(arr))))
+        }
+    };
+    iter(arr, [])
+};
+let reduce = fn(arr, initial, f) {
+    let iter = fn(arr, result) {
+        if (len(arr) == 0) {
+            result
+        } else {
+            iter(rest(arr), f(result, first(arr)))
+        }
+    };
+    iter(arr, initial)
+};
+
+let numbers = range(1, 6);
+puts(numbers, map(numbers, fn(x) { x * x }));
+puts(reduce(numbers, 0, fn(acc, x) { acc + x }));
+puts(first([]), last(numbers), rest([]), numbers[10], numbers[-1]);
+
+let people = [{"name": "Alice", "age": 24}, {"name": "Anna", "age": 28}];
+puts(people[1]["name"], people[0]["age"] + 1, people[0]["missing"]);
+puts({true: "yes", 1: "one", "two": 2}, {}[1]);
+puts(type(people), type(people[0]), type(len), type(1), str(12) + "!", int("42") + 1);
+puts(len("hello"), len(people), len(people[0]));
@@ -0,0 +1,11 @@
+// Comments are skipped by both engines and kept by the formatter.
+
+/* A block comment /* can nest */ and span
+   several lines. */
+let area = fn(w, h) {
+    // the last expression is the value
+    w * h
+};
+
+puts(area(3, 4)); // 12
+puts(10 / 2, "// not a comment"); /* ends here */ // and so does this
@@ -0,0 +1,15 @@
+let unless = macro(condition, consequence, alternative) {
+    quote(if (!unquote(condition)) {
+        unquote(consequence)
+    } else {
+        unquote(alternative)
+    })
+};
+unless(10 > 5, puts("not greater"), puts("greater"));
+
+let square = macro(x) { quote(unquote(x) * unquote(x)) };
+let n = 7;
+puts(square(n + 1), square(square(2)));
+
+let constant = macro() { quote(unquote(len("folded at expansion"))) };
+puts(constant());
@@ -0,0 +1,18 @@
+let fibonacci = fn(x) {
+    if (x < 2) { return x; }
+    fibonacci(x - 1) + fibonacci(x - 2)
+};
+puts(fibonacci(15));
+
+let wrapper = fn() {
+    let countDown = fn(x) {
+        if (x == 0) { return "done"; }
+        countDown(x - 1)
+    };
+    countDown(50)
+};
+puts(wrapper());
+
+let isEven = fn(n) { if (n == 0) { true } else { isOdd(n - 1) } };
+let isOdd = fn(n) { if (n == 0) { false } else { isEven(n - 1) } };
+puts(isEven(10), isOdd(7));
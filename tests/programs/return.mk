@@ -0,0 +1,15 @@
+let early = fn(x) {
+    if (x > 10) {
+        if (x > 100) { return "huge"; }
+        return "big";
+    }
+    "small"
+};
+puts(early(1), early(11), early(101));
+let nothing = fn() {};
+puts(nothing(), fn() {
+    let x = 1;
+}());
+puts("before");
+return 1;
+puts("not reached");
@@ -0,0 +1,19 @@
+// a function looks its globals up when it runs, so it sees ones defined after
+// it, and calls whatever its own name is bound to by then
+let total = fn() { base + 1 };
+let base = 1;
+puts(total());
+
+let even = fn(n) { if (n == 0) { true } else { odd(n - 1) } };
+let odd = fn(n) { if (n == 0) { false } else { even(n - 1) } };
+puts([even(4), odd(4)], [even(7), odd(7)]);
+
+let f = fn(n) { if (n == 0) { 0 } else { f(n - 1) } };
+let g = f;
+let f = fn(n) { 100 };
+puts(g(3));
+
+// a closure keeps the variables of the call that made it
+let adder = fn(a) { fn(b) { a + b } };
+let addTwo = adder(2);
+puts(addTwo(3), adder(10)(3));
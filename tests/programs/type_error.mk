@@ -0,0 +1,6 @@
+let values = [1, 2, 3];
+puts(values);
+let total = fn(arr) { arr[0] + arr[1] + true };
+puts("about to fail");
+total(values);
+puts("not reached");
@@ -0,0 +1,5 @@
+// Identifiers can use any letters, and digits after the first character.
+let größe = 3;
+let 数量 = [1, 2];
+let x1 = größe * len(数量);
+puts(x1, "größe", "数量");